@@ -0,0 +1,486 @@
+use crate::{
+    common::{BloomParams, HashIndexIterator},
+    runtime_size::BloomFilter,
+    utils::{read_varint, write_varint, HexFieldDebug},
+};
+use bitvec::{prelude::Lsb0, view::BitView};
+use std::{fmt::Debug, iter::Peekable, mem::size_of, slice};
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A bloom filter that behaves exactly like a [`runtime_size::BloomFilter`](BloomFilter),
+/// but stores its set bits sparsely while it's mostly empty.
+///
+/// A freshly created filter doesn't allocate its full size. Instead it keeps a sorted,
+/// delta-encoded list of the indices of its set bits, and only switches to a dense bit
+/// array once that list grows past a threshold. Similar to sparse HyperLogLog
+/// implementations, new indices go into a small sorted buffer first, which is merged
+/// into the list once it grows past the square root of the list's length. The list
+/// restarts its deltas every few entries, so queries only decode a single block of it.
+///
+/// The representation is an implementation detail: membership queries, equality and
+/// the bytes returned by [`to_bytes`](AdaptiveBloomFilter::to_bytes) are the same as
+/// for a [`runtime_size::BloomFilter`](BloomFilter) with the same parameters and items.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{adaptive::AdaptiveBloomFilter, runtime_size::BloomFilter};
+///
+/// let mut filter = AdaptiveBloomFilter::new_from_fpr(10_000_000, 0.0001);
+/// let mut dense = BloomFilter::new_from_fpr(10_000_000, 0.0001);
+///
+/// for i in 0u32..10 {
+///     filter.insert(&i.to_le_bytes());
+///     dense.insert(&i.to_le_bytes());
+/// }
+///
+/// assert!(filter.is_sparse());
+/// assert!(filter.contains(&5u32.to_le_bytes()));
+/// assert_eq!(filter.to_bytes(), dense.as_bytes());
+/// ```
+#[derive(Clone)]
+pub struct AdaptiveBloomFilter {
+    k_hashes: usize,
+    byte_size: usize,
+    sparse_limit: usize,
+    repr: Repr,
+}
+
+#[derive(Clone)]
+enum Repr {
+    /// Disjoint, sorted lists of set bit indices
+    Sparse {
+        ones: EncodedIndices,
+        /// Recently set indices that aren't merged into `ones` yet
+        buffer: Vec<usize>,
+    },
+    Dense(Box<[u8]>),
+}
+
+/// A sorted list of indices, stored as varint-encoded gaps between them.
+///
+/// Every [`BLOCK_LEN`] entries, the gaps restart from zero, and the block's first index
+/// and byte offset are recorded, so lookups can skip to the right block.
+#[derive(Clone, Default)]
+struct EncodedIndices {
+    bytes: Vec<u8>,
+    len: usize,
+    /// The first index and byte offset of each block
+    blocks: Vec<(usize, usize)>,
+}
+
+/// Decodes the indices of an [`EncodedIndices`] starting at some block.
+struct EncodedIter<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    position: usize,
+    len: usize,
+    next: usize,
+}
+
+/// The smallest size the insert buffer of sparse filters may grow to before it's merged.
+const MIN_BUFFER_LEN: usize = 32;
+
+/// The number of entries of [`EncodedIndices`] that share a block.
+const BLOCK_LEN: usize = 64;
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl AdaptiveBloomFilter {
+    /// Construct a filter with optimal parameters for given maximum capacity `n_elems`
+    /// and false positive rate `fpr`. See [`BloomFilter::new_from_fpr`].
+    pub fn new_from_fpr(n_elems: u64, fpr: f64) -> Self {
+        Self::new_with_params(BloomParams::new_from_fpr(n_elems, fpr))
+    }
+
+    /// Construct an optimal power-of-two sized filter for given maximum capacity `n_elems`
+    /// and false positive rate `fpr`. See [`BloomFilter::new_from_fpr_po2`].
+    pub fn new_from_fpr_po2(n_elems: u64, fpr: f64) -> Self {
+        Self::new_with_params(BloomParams::new_from_fpr_po2(n_elems, fpr))
+    }
+
+    /// Construct a filter with given target size and target capacity.
+    /// See [`BloomFilter::new_from_size`].
+    pub fn new_from_size(bloom_bytes: usize, n_elems: u64) -> Self {
+        Self::new_with_params(BloomParams::new_from_size(bloom_bytes, n_elems))
+    }
+
+    /// Construct an empty filter with given parameters.
+    ///
+    /// The filter switches to the dense representation once its sparse lists of set
+    /// bit indices take up more than a quarter of `params.byte_size`.
+    pub fn new_with_params(params: BloomParams) -> Self {
+        Self {
+            k_hashes: params.k_hashes,
+            byte_size: params.byte_size,
+            sparse_limit: params.byte_size / 4,
+            repr: Repr::Sparse {
+                ones: EncodedIndices::default(),
+                buffer: Vec::new(),
+            },
+        }
+    }
+
+    /// Set the size in bytes that the sparse lists of set bit indices may grow to before
+    /// this filter switches to the dense representation.
+    ///
+    /// If the sparse list is already larger than that, this switches immediately.
+    /// Dense filters never switch back.
+    pub fn set_sparse_limit(&mut self, sparse_limit: usize) {
+        self.sparse_limit = sparse_limit;
+        self.densify_if_needed();
+    }
+
+    /// Compute the bloom parameters for this bloom filter.
+    pub fn get_bloom_params(&self) -> BloomParams {
+        BloomParams {
+            k_hashes: self.k_hashes,
+            byte_size: self.byte_size,
+        }
+    }
+
+    /// Get the approximate false positive rate at given capacity for this bloom filter.
+    pub fn false_positive_rate_at(&self, n_elems: u64) -> f64 {
+        self.get_bloom_params().false_positive_rate_at(n_elems)
+    }
+
+    /// Insert an element into the bloom filter.
+    pub fn insert(&mut self, item: &impl AsRef<[u8]>) {
+        let indices = self.hash_indices(item).collect::<Vec<_>>();
+        match &mut self.repr {
+            Repr::Dense(bytes) => {
+                for i in indices {
+                    bytes.view_bits_mut::<Lsb0>().set(i, true);
+                }
+            }
+            Repr::Sparse { ones, buffer } => {
+                for i in indices {
+                    if ones.contains(i) {
+                        continue;
+                    }
+                    if let Err(position) = buffer.binary_search(&i) {
+                        buffer.insert(position, i);
+                    }
+                }
+
+                let buffer_limit = MIN_BUFFER_LEN.max((ones.len as f64).sqrt() as usize);
+                if buffer.len() > buffer_limit {
+                    *ones = SparseIter::new(ones, buffer).collect();
+                    buffer.clear();
+                }
+                self.densify_if_needed();
+            }
+        }
+    }
+
+    /// Check whether an element was (probably) added into the bloom filter.
+    pub fn contains(&self, item: &impl AsRef<[u8]>) -> bool {
+        match &self.repr {
+            Repr::Dense(bytes) => self
+                .hash_indices(item)
+                .all(|i| bytes.view_bits::<Lsb0>()[i]),
+            Repr::Sparse { ones, buffer } => self
+                .hash_indices(item)
+                .all(|i| ones.contains(i) || buffer.binary_search(&i).is_ok()),
+        }
+    }
+
+    /// Counts the amount of bits set in the bloom filter.
+    pub fn count_ones(&self) -> usize {
+        match &self.repr {
+            Repr::Dense(bytes) => bytes.view_bits::<Lsb0>().count_ones(),
+            Repr::Sparse { ones, buffer } => ones.len + buffer.len(),
+        }
+    }
+
+    /// Returns the indices of all set bits in ascending order.
+    pub fn iter_ones(&self) -> impl Iterator<Item = usize> + '_ {
+        let (sparse, dense) = match &self.repr {
+            Repr::Sparse { ones, buffer } => (Some(SparseIter::new(ones, buffer)), None),
+            Repr::Dense(bytes) => (None, Some(bytes.view_bits::<Lsb0>().iter_ones())),
+        };
+
+        sparse
+            .into_iter()
+            .flatten()
+            .chain(dense.into_iter().flatten())
+    }
+
+    /// Returns how many hash function invocations are used per item inserted.
+    pub fn hash_count(&self) -> usize {
+        self.k_hashes
+    }
+
+    /// Returns true while the filter uses the sparse representation.
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.repr, Repr::Sparse { .. })
+    }
+
+    /// Returns the canonical bytes of this filter, i.e. exactly what
+    /// [`BloomFilter::as_bytes`] would return for the same items.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match &self.repr {
+            Repr::Dense(bytes) => bytes.to_vec(),
+            Repr::Sparse { .. } => self.dense_bytes().into_vec(),
+        }
+    }
+
+    /// Returns the indices that a given element would set in the filter.
+    pub fn hash_indices<'a>(&self, item: &'a impl AsRef<[u8]>) -> impl Iterator<Item = usize> + 'a {
+        HashIndexIterator::new(item, self.byte_size * 8).take(self.k_hashes)
+    }
+
+    fn dense_bytes(&self) -> Box<[u8]> {
+        let mut bytes = vec![0u8; self.byte_size].into_boxed_slice();
+        for i in self.iter_ones() {
+            bytes.view_bits_mut::<Lsb0>().set(i, true);
+        }
+        bytes
+    }
+
+    fn densify_if_needed(&mut self) {
+        if let Repr::Sparse { ones, buffer } = &self.repr {
+            if ones.size() + buffer.len() * size_of::<usize>() > self.sparse_limit {
+                self.repr = Repr::Dense(self.dense_bytes());
+            }
+        }
+    }
+}
+
+impl From<BloomFilter> for AdaptiveBloomFilter {
+    fn from(filter: BloomFilter) -> Self {
+        let params = filter.get_bloom_params();
+        Self {
+            k_hashes: params.k_hashes,
+            byte_size: params.byte_size,
+            sparse_limit: params.byte_size / 4,
+            repr: Repr::Dense(Box::from(filter.as_bytes())),
+        }
+    }
+}
+
+impl From<AdaptiveBloomFilter> for BloomFilter {
+    fn from(filter: AdaptiveBloomFilter) -> Self {
+        let bytes = match filter.repr {
+            Repr::Dense(bytes) => bytes,
+            Repr::Sparse { .. } => filter.dense_bytes(),
+        };
+        BloomFilter::new_with(filter.k_hashes, bytes)
+    }
+}
+
+impl PartialEq for AdaptiveBloomFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.k_hashes == other.k_hashes
+            && self.byte_size == other.byte_size
+            && self.iter_ones().eq(other.iter_ones())
+    }
+}
+
+impl Eq for AdaptiveBloomFilter {}
+
+impl Debug for AdaptiveBloomFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut debug = f.debug_struct("AdaptiveBloomFilter");
+        debug.field("k_hashes", &self.k_hashes);
+        match &self.repr {
+            Repr::Sparse { .. } => debug
+                .field("byte_size", &self.byte_size)
+                .field("ones", &self.iter_ones().collect::<Vec<_>>()),
+            Repr::Dense(bytes) => debug.field("bytes", &HexFieldDebug(bytes)),
+        };
+        debug.finish()
+    }
+}
+
+impl EncodedIndices {
+    /// Returns whether `index` is in the list, decoding at most one block.
+    fn contains(&self, index: usize) -> bool {
+        let block = self.blocks.partition_point(|&(first, _)| first <= index);
+        if block == 0 {
+            return false;
+        }
+
+        self.iter_from(block - 1)
+            .take(BLOCK_LEN)
+            .take_while(|&one| one <= index)
+            .any(|one| one == index)
+    }
+
+    /// Returns the number of bytes this list takes up.
+    fn size(&self) -> usize {
+        self.bytes.len() + self.blocks.len() * size_of::<(usize, usize)>()
+    }
+
+    fn iter(&self) -> EncodedIter<'_> {
+        self.iter_from(0)
+    }
+
+    fn iter_from(&self, block: usize) -> EncodedIter<'_> {
+        EncodedIter {
+            bytes: &self.bytes,
+            offset: self
+                .blocks
+                .get(block)
+                .map_or(self.bytes.len(), |&(_, offset)| offset),
+            position: block * BLOCK_LEN,
+            len: self.len,
+            next: 0,
+        }
+    }
+}
+
+impl FromIterator<usize> for EncodedIndices {
+    /// Encodes indices given in ascending order.
+    fn from_iter<T: IntoIterator<Item = usize>>(iter: T) -> Self {
+        let mut encoded = Self::default();
+        let mut next = 0;
+        for index in iter {
+            if encoded.len % BLOCK_LEN == 0 {
+                encoded.blocks.push((index, encoded.bytes.len()));
+                next = 0;
+            }
+            write_varint(&mut encoded.bytes, (index - next) as u64);
+            next = index + 1;
+            encoded.len += 1;
+        }
+        encoded
+    }
+}
+
+impl Iterator for EncodedIter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position == self.len {
+            return None;
+        }
+        if self.position % BLOCK_LEN == 0 {
+            self.next = 0;
+        }
+
+        let gap = read_varint(self.bytes, &mut self.offset).expect("encoded by from_iter");
+        let index = self.next + gap as usize;
+        self.next = index + 1;
+        self.position += 1;
+        Some(index)
+    }
+}
+
+/// Merges the two sorted lists of indices of a sparse representation.
+struct SparseIter<'a> {
+    ones: Peekable<EncodedIter<'a>>,
+    buffer: Peekable<slice::Iter<'a, usize>>,
+}
+
+impl<'a> SparseIter<'a> {
+    fn new(ones: &'a EncodedIndices, buffer: &'a [usize]) -> Self {
+        Self {
+            ones: ones.iter().peekable(),
+            buffer: buffer.iter().peekable(),
+        }
+    }
+}
+
+impl Iterator for SparseIter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match (self.ones.peek(), self.buffer.peek()) {
+            (Some(one), Some(&&buffered)) if buffered < *one => self.buffer.next().copied(),
+            (Some(_), _) => self.ones.next(),
+            (None, _) => self.buffer.next().copied(),
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveBloomFilter, Repr};
+
+    #[test]
+    fn switches_to_dense_representation() {
+        let mut filter = AdaptiveBloomFilter::new_from_fpr(1000, 0.01);
+        assert!(filter.is_sparse());
+
+        for i in 0u32..1000 {
+            filter.insert(&i.to_le_bytes());
+        }
+
+        assert!(!filter.is_sparse());
+    }
+
+    #[test]
+    fn buffered_indices_are_merged() {
+        let mut filter = AdaptiveBloomFilter::new_from_fpr(10_000_000, 0.01);
+        for i in 0u32..10_000 {
+            filter.insert(&i.to_le_bytes());
+        }
+
+        assert!(filter.is_sparse());
+        assert!(filter
+            .iter_ones()
+            .zip(filter.iter_ones().skip(1))
+            .all(|(a, b)| a < b));
+        assert!((0u32..10_000).all(|i| filter.contains(&i.to_le_bytes())));
+
+        let ones = match &filter.repr {
+            Repr::Sparse { ones, .. } => ones,
+            Repr::Dense(_) => panic!("expected a sparse filter"),
+        };
+        assert!(ones.size() < ones.len * 4);
+        assert!(ones.iter().all(|i| ones.contains(i)));
+    }
+
+    #[test]
+    fn lowering_the_limit_switches_immediately() {
+        let mut filter = AdaptiveBloomFilter::new_from_fpr(1000, 0.01);
+        filter.insert(b"Hello");
+        filter.set_sparse_limit(0);
+        assert!(!filter.is_sparse());
+        assert!(filter.contains(b"Hello"));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::AdaptiveBloomFilter;
+    use crate::runtime_size::BloomFilter;
+    use proptest::{prop_assert, prop_assert_eq};
+    use test_strategy::proptest;
+
+    #[proptest]
+    fn behaves_like_dense_filter(
+        items: Vec<u64>,
+        #[strategy(10usize..1_000)] size: usize,
+        #[strategy(0usize..300)] sparse_limit: usize,
+    ) {
+        let capacity = std::cmp::max(items.len() as u64, 1);
+        let mut adaptive = AdaptiveBloomFilter::new_from_size(size, capacity);
+        let mut dense = BloomFilter::new_from_size(size, capacity);
+        adaptive.set_sparse_limit(sparse_limit);
+
+        for item in items.iter() {
+            adaptive.insert(&item.to_le_bytes());
+            dense.insert(&item.to_le_bytes());
+
+            prop_assert_eq!(adaptive.count_ones(), dense.count_ones());
+        }
+
+        for item in items.iter() {
+            prop_assert!(adaptive.contains(&item.to_le_bytes()));
+        }
+        prop_assert_eq!(adaptive.to_bytes(), dense.as_bytes());
+        prop_assert_eq!(&adaptive, &AdaptiveBloomFilter::from(dense.clone()));
+        prop_assert_eq!(BloomFilter::from(adaptive), dense);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{
+    common::{BloomParams, Error},
+    runtime_size::BloomFilter,
+};
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// An [attenuated bloom filter] for probabilistic routing in peer-to-peer networks.
+///
+/// It consists of `depth` layers of [`BloomFilter`]s with identical parameters,
+/// where layer `i` summarizes all items that are reachable within `i` hops.
+/// Layer `0` thus only contains local items.
+///
+/// Peers exchange their attenuated filters and merge their neighbours' filters
+/// into their own with [`merge_from_neighbour`](AttenuatedBloomFilter::merge_from_neighbour),
+/// which shifts each of the neighbour's layers one hop further away.
+/// Items that are more than `depth - 1` hops away fall off the end.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{attenuated::AttenuatedBloomFilter, common::BloomParams};
+///
+/// let params = BloomParams::new_from_fpr(1000, 0.001);
+///
+/// // A chain of peers: alice <-> bob <-> carol
+/// let mut carol = AttenuatedBloomFilter::new(3, params.clone()).unwrap();
+/// carol.insert(b"block from carol");
+///
+/// let mut bob = AttenuatedBloomFilter::new(3, params.clone()).unwrap();
+/// bob.insert(b"block from bob");
+/// bob.merge_from_neighbour(&carol).unwrap();
+///
+/// let mut alice = AttenuatedBloomFilter::new(3, params).unwrap();
+/// alice.merge_from_neighbour(&bob).unwrap();
+///
+/// assert_eq!(alice.query(b"block from bob"), Some(1));
+/// assert_eq!(alice.query(b"block from carol"), Some(2));
+/// assert_eq!(alice.query(b"unknown block"), None);
+/// ```
+///
+/// [attenuated bloom filter]: https://doi.org/10.1109/INFCOM.2002.1019375
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttenuatedBloomFilter {
+    layers: Vec<BloomFilter>,
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl AttenuatedBloomFilter {
+    /// Construct an empty attenuated bloom filter with `depth` layers, each of which
+    /// is a bloom filter with given parameters.
+    ///
+    /// Fails if `depth` is zero.
+    pub fn new(depth: usize, params: BloomParams) -> Result<Self, Error> {
+        if depth == 0 {
+            return Err(Error::ZeroDepth);
+        }
+
+        let layer = BloomFilter::new_with(
+            params.k_hashes,
+            vec![0u8; params.byte_size].into_boxed_slice(),
+        );
+
+        Ok(Self {
+            layers: vec![layer; depth],
+        })
+    }
+
+    /// Construct the attenuated bloom filter from existing layers, starting with
+    /// the one for local items.
+    ///
+    /// Fails if there are no layers or if they don't all have the same parameters.
+    pub fn new_with(layers: Vec<BloomFilter>) -> Result<Self, Error> {
+        let expected = layers.first().ok_or(Error::ZeroDepth)?.get_bloom_params();
+        for layer in layers.iter() {
+            let actual = layer.get_bloom_params();
+            if actual != expected {
+                return Err(Error::BloomParamsMismatch { expected, actual });
+            }
+        }
+
+        Ok(Self { layers })
+    }
+
+    /// Insert a locally available item.
+    ///
+    /// Since local items are reachable within any number of hops, this adds the
+    /// item to every layer.
+    pub fn insert(&mut self, item: &impl AsRef<[u8]>) {
+        for layer in self.layers.iter_mut() {
+            layer.insert(item);
+        }
+    }
+
+    /// Returns the smallest number of hops within which given item is (probably)
+    /// reachable, or `None` if it's not reachable within `depth - 1` hops.
+    pub fn query(&self, item: &impl AsRef<[u8]>) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.contains(item))
+    }
+
+    /// Merge a neighbour's attenuated filter into this one.
+    ///
+    /// Everything reachable from the neighbour within `i` hops is reachable from
+    /// here within `i + 1` hops, so the neighbour's layer `i` is added to this filter's
+    /// layer `i + 1`. The neighbour's last layer is dropped.
+    ///
+    /// Fails if the neighbour's layers have different parameters or if its depth differs.
+    pub fn merge_from_neighbour(&mut self, neighbour: &AttenuatedBloomFilter) -> Result<(), Error> {
+        self.check_compatible(neighbour)?;
+
+        for (layer, neighbour_layer) in self.layers.iter_mut().skip(1).zip(neighbour.layers.iter())
+        {
+            layer.union_with(neighbour_layer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns a copy of this filter as seen from a neighbour, i.e. with every layer
+    /// moved one hop further away and an empty layer `0`.
+    ///
+    /// This is what a peer would store per link to route requests towards this peer.
+    pub fn shifted(&self) -> Self {
+        let mut layers = Vec::with_capacity(self.layers.len());
+        layers.push(self.empty_layer());
+        layers.extend(self.layers.iter().take(self.layers.len() - 1).cloned());
+
+        Self { layers }
+    }
+
+    /// Returns the number of layers.
+    pub fn depth(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Returns the layers, starting with the one for local items.
+    pub fn layers(&self) -> &[BloomFilter] {
+        &self.layers
+    }
+
+    fn check_compatible(&self, other: &AttenuatedBloomFilter) -> Result<(), Error> {
+        if self.depth() != other.depth() {
+            return Err(Error::DepthMismatch {
+                expected: self.depth(),
+                actual: other.depth(),
+            });
+        }
+
+        let (expected, actual) = (
+            self.layers[0].get_bloom_params(),
+            other.layers[0].get_bloom_params(),
+        );
+        if expected != actual {
+            return Err(Error::BloomParamsMismatch { expected, actual });
+        }
+
+        Ok(())
+    }
+
+    fn empty_layer(&self) -> BloomFilter {
+        let params = self.layers[0].get_bloom_params();
+        BloomFilter::new_with(
+            params.k_hashes,
+            vec![0u8; params.byte_size].into_boxed_slice(),
+        )
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params() -> BloomParams {
+        BloomParams::new_from_fpr(100, 0.0001)
+    }
+
+    #[test]
+    fn items_fall_off_after_depth_hops() {
+        let mut peers = (0..4)
+            .map(|_| AttenuatedBloomFilter::new(3, params()).unwrap())
+            .collect::<Vec<_>>();
+        peers[3].insert(b"far away");
+
+        for i in (0..3).rev() {
+            let neighbour = peers[i + 1].clone();
+            peers[i].merge_from_neighbour(&neighbour).unwrap();
+        }
+
+        assert_eq!(peers[2].query(b"far away"), Some(1));
+        assert_eq!(peers[1].query(b"far away"), Some(2));
+        assert_eq!(peers[0].query(b"far away"), None);
+    }
+
+    #[test]
+    fn merging_equals_union_with_shifted() {
+        let mut a = AttenuatedBloomFilter::new(4, params()).unwrap();
+        let mut b = AttenuatedBloomFilter::new(4, params()).unwrap();
+        a.insert(b"a");
+        b.insert(b"b");
+
+        let mut merged = a.clone();
+        merged.merge_from_neighbour(&b).unwrap();
+
+        let shifted = b.shifted();
+        for (i, layer) in merged.layers().iter().enumerate() {
+            let mut expected = a.layers()[i].clone();
+            expected.union_with(&shifted.layers()[i]).unwrap();
+            assert_eq!(layer, &expected);
+        }
+    }
+
+    #[test]
+    fn zero_depth_is_rejected() {
+        assert!(matches!(
+            AttenuatedBloomFilter::new(0, params()),
+            Err(Error::ZeroDepth)
+        ));
+        assert!(matches!(
+            AttenuatedBloomFilter::new_with(Vec::new()),
+            Err(Error::ZeroDepth)
+        ));
+    }
+
+    #[test]
+    fn mismatching_depths_are_rejected() {
+        let mut a = AttenuatedBloomFilter::new(3, params()).unwrap();
+        let b = AttenuatedBloomFilter::new(4, params()).unwrap();
+        assert!(matches!(
+            a.merge_from_neighbour(&b),
+            Err(Error::DepthMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,338 @@
+use crate::{
+    common::{Error, HashIndexIterator},
+    const_size::BloomFilter,
+    utils::{DeserializeBytes, SerializeBytes},
+};
+use serde::{de::Error as _, Deserialize, Serialize};
+use std::fmt::Debug;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A bit-sliced signature index over many [`BloomFilter`]s of the same size.
+///
+/// Instead of storing each filter as a contiguous bit array, the index stores one
+/// row per bit position, where the `i`-th bit of a row is the bit of the `i`-th
+/// filter at that position (see [BitFunnel]).
+/// A membership query then only needs to AND the `K` rows for the item's hash
+/// indices to find all filters that (probably) contain it.
+///
+/// Filters are identified by the order they were appended in, starting at zero.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{bit_sliced::BitSlicedIndex, const_size::BloomFilter};
+///
+/// let mut index = BitSlicedIndex::<256, 30>::new();
+///
+/// for doc in ["apples and pears", "pears and plums", "plums and cherries"] {
+///     let mut filter = BloomFilter::new();
+///     for word in doc.split(' ') {
+///         filter.insert(&word);
+///     }
+///     index.push(&filter);
+/// }
+///
+/// assert_eq!(index.query(&"pears"), vec![0, 1]);
+/// assert_eq!(index.query(&"cherries"), vec![2]);
+/// assert!(index.query(&"bananas").is_empty());
+/// ```
+///
+/// [BitFunnel]: https://danluu.com/bitfunnel-sigir.pdf
+#[derive(Clone, PartialEq, Eq)]
+pub struct BitSlicedIndex<const N: usize, const K: usize> {
+    len: usize,
+    rows: Vec<Vec<u64>>,
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl<const N: usize, const K: usize> BitSlicedIndex<N, K> {
+    /// Creates a new index that doesn't contain any filters.
+    pub fn new() -> Self {
+        Self {
+            len: 0,
+            rows: vec![Vec::new(); N * 8],
+        }
+    }
+
+    /// Appends a filter to the index and returns the id it can be found under.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::{bit_sliced::BitSlicedIndex, const_size::BloomFilter};
+    ///
+    /// let mut index = BitSlicedIndex::<256, 30>::new();
+    ///
+    /// assert_eq!(index.push(&BloomFilter::new()), 0);
+    /// assert_eq!(index.push(&BloomFilter::new()), 1);
+    /// assert_eq!(index.len(), 2);
+    /// ```
+    pub fn push(&mut self, filter: &BloomFilter<N, K>) -> usize {
+        let id = self.len;
+        let (word, bit) = (id / 64, id % 64);
+
+        if bit == 0 {
+            for row in self.rows.iter_mut() {
+                row.push(0);
+            }
+        }
+
+        for position in filter.bits.iter_ones() {
+            self.rows[position][word] |= 1 << bit;
+        }
+
+        self.len += 1;
+        id
+    }
+
+    /// Returns the ids of all filters that (probably) contain given item, in ascending order.
+    ///
+    /// This gives the same result as calling [`BloomFilter::contains`] on each
+    /// filter individually.
+    pub fn query<T: AsRef<[u8]>>(&self, item: &T) -> Vec<usize> {
+        let mut indices = HashIndexIterator::new(item, N * 8).take(K);
+
+        let mut matches = match indices.next() {
+            Some(first) => self.rows[first].clone(),
+            // Filters without bits "contain" anything
+            None => return (0..self.len).collect(),
+        };
+
+        for position in indices {
+            for (acc, word) in matches.iter_mut().zip(self.rows[position].iter()) {
+                *acc &= word;
+            }
+        }
+
+        matches
+            .iter()
+            .enumerate()
+            .flat_map(|(word_index, &word)| {
+                (0..64)
+                    .filter(move |bit| word & (1 << bit) != 0)
+                    .map(move |bit| word_index * 64 + bit)
+            })
+            .collect()
+    }
+
+    /// Reconstructs the filter with given id, if it exists.
+    pub fn get(&self, id: usize) -> Option<BloomFilter<N, K>> {
+        if id >= self.len {
+            return None;
+        }
+
+        let (word, bit) = (id / 64, id % 64);
+        let mut filter = BloomFilter::new();
+        for (position, row) in self.rows.iter().enumerate() {
+            filter.bits.set(position, row[word] & (1 << bit) != 0);
+        }
+
+        Some(filter)
+    }
+
+    /// Returns the number of filters in this index.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if no filters were added to this index yet.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the number of 64-bit words that each row consists of.
+    fn words_per_row(&self) -> usize {
+        self.len / 64 + usize::from(self.len % 64 != 0)
+    }
+}
+
+impl<const N: usize, const K: usize> Default for BitSlicedIndex<N, K> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, const K: usize> Serialize for BitSlicedIndex<N, K> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let bytes = self
+            .rows
+            .iter()
+            .flatten()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        (self.len as u64, SerializeBytes(&bytes)).serialize(serializer)
+    }
+}
+
+impl<'de, const N: usize, const K: usize> Deserialize<'de> for BitSlicedIndex<N, K> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (len, DeserializeBytes(bytes)) = <(u64, DeserializeBytes)>::deserialize(deserializer)?;
+        let malformed = |reason| D::Error::custom(Error::MalformedEncoding { reason });
+
+        let mut index = Self {
+            len: usize::try_from(len).map_err(|_| malformed("too many filters"))?,
+            rows: Vec::new(),
+        };
+
+        let row_bytes = index.words_per_row() * 8;
+        let expected = row_bytes
+            .checked_mul(N * 8)
+            .ok_or_else(|| malformed("too many filters"))?;
+        if bytes.len() != expected {
+            return Err(D::Error::invalid_length(
+                bytes.len(),
+                &format!("{expected} bytes for {len} filters").as_str(),
+            ));
+        }
+
+        index.rows = bytes
+            .chunks_exact(row_bytes.max(1))
+            .map(|row| {
+                row.chunks_exact(8)
+                    .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+                    .collect()
+            })
+            .collect();
+        // Empty indices serialize no row bytes at all
+        index.rows.resize(N * 8, Vec::new());
+
+        // Bits past the last filter must be unset, otherwise queries would return their ids
+        let used_bits = index.len % 64;
+        if used_bits != 0
+            && index
+                .rows
+                .iter()
+                .any(|row| row[row.len() - 1] >> used_bits != 0)
+        {
+            return Err(malformed("bits set past the last filter"));
+        }
+
+        Ok(index)
+    }
+}
+
+impl<const N: usize, const K: usize> Debug for BitSlicedIndex<N, K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BitSlicedIndex")
+            .field("len", &self.len)
+            .field("rows", &self.rows.len())
+            .finish()
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter_with(items: &[&str]) -> BloomFilter<256, 30> {
+        let mut filter = BloomFilter::new();
+        for item in items {
+            filter.insert(item);
+        }
+        filter
+    }
+
+    #[test]
+    fn filters_can_be_reconstructed() {
+        let mut index = BitSlicedIndex::<256, 30>::new();
+        let filters = (0..100)
+            .map(|i| filter_with(&[&i.to_string()]))
+            .collect::<Vec<_>>();
+
+        for filter in filters.iter() {
+            index.push(filter);
+        }
+
+        for (id, filter) in filters.iter().enumerate() {
+            assert_eq!(index.get(id).as_ref(), Some(filter));
+        }
+        assert_eq!(index.get(100), None);
+    }
+
+    #[test]
+    fn serialized_index_can_be_deserialized_correctly() {
+        let mut index = BitSlicedIndex::<256, 30>::new();
+        for i in 0..70 {
+            index.push(&filter_with(&[&i.to_string(), "common"]));
+        }
+
+        let ipld = libipld::serde::to_ipld(&index).unwrap();
+        let deserialized: BitSlicedIndex<256, 30> = libipld::serde::from_ipld(ipld).unwrap();
+
+        assert_eq!(deserialized, index);
+        assert_eq!(deserialized.query(&"common").len(), 70);
+    }
+
+    #[test]
+    fn invalid_encodings_are_rejected() {
+        let mut index = BitSlicedIndex::<256, 30>::new();
+        index.push(&filter_with(&["Hello"]));
+        index.rows[0][0] |= 1 << 1;
+        let ipld = libipld::serde::to_ipld(&index).unwrap();
+        assert!(libipld::serde::from_ipld::<BitSlicedIndex<256, 30>>(ipld).is_err());
+
+        let ipld = libipld::serde::to_ipld((u64::MAX, SerializeBytes(&[]))).unwrap();
+        assert!(libipld::serde::from_ipld::<BitSlicedIndex<256, 30>>(ipld).is_err());
+    }
+
+    #[test]
+    fn empty_index_round_trips() {
+        let index = BitSlicedIndex::<256, 30>::new();
+        let ipld = libipld::serde::to_ipld(&index).unwrap();
+        let deserialized: BitSlicedIndex<256, 30> = libipld::serde::from_ipld(ipld).unwrap();
+
+        assert_eq!(deserialized, index);
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::BitSlicedIndex;
+    use crate::const_size::BloomFilter;
+    use proptest::{collection::vec, prop_assert_eq};
+    use test_strategy::proptest;
+
+    #[proptest(cases = 100)]
+    fn query_matches_individual_contains(
+        #[strategy(vec(vec(0u8..20, 0..10), 0..150))] docs: Vec<Vec<u8>>,
+        #[strategy(0u8..20)] item: u8,
+    ) {
+        let mut index = BitSlicedIndex::<64, 4>::new();
+        let mut filters = Vec::new();
+        for doc in docs.iter() {
+            let mut filter = BloomFilter::<64, 4>::new();
+            for word in doc.iter() {
+                filter.insert(&[*word]);
+            }
+            index.push(&filter);
+            filters.push(filter);
+        }
+
+        let expected = filters
+            .iter()
+            .enumerate()
+            .filter(|(_, filter)| filter.contains(&[item]))
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+
+        prop_assert_eq!(index.query(&[item]), expected);
+    }
+}
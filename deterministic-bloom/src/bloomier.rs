@@ -0,0 +1,331 @@
+use crate::{common::Error, utils::HexFieldDebug};
+use std::{collections::BTreeMap, fmt::Debug};
+use xxhash_rust::xxh3;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A static [Bloomier filter], i.e. an approximate key-value map that stores
+/// only a couple of bits per key.
+///
+/// It is built once from a complete list of `(key, value)` pairs and can't be
+/// modified afterwards. Looking up a key that was part of the construction always
+/// returns its value. Looking up any other key returns an arbitrary value, unless
+/// the filter was built with check bits, in which case such lookups are detected
+/// as absent in all but `2^-check_bits` of the cases.
+///
+/// Construction is deterministic: the same set of pairs always produces the same
+/// filter, independent of the order they're given in.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::bloomier::BloomierFilter;
+///
+/// let pairs = [(b"alice", 1), (b"bobby", 2), (b"carol", 3)];
+/// let filter = BloomierFilter::from_pairs(pairs, 2, 8).unwrap();
+///
+/// assert_eq!(filter.get(b"alice"), Some(1));
+/// assert_eq!(filter.get(b"carol"), Some(3));
+/// assert_eq!(filter.get(b"dave"), None); // true in all but 1 in 256 cases
+/// ```
+///
+/// [Bloomier filter]: https://en.wikipedia.org/wiki/Bloom_filter#Bloomier_filters
+#[derive(Clone, PartialEq, Eq)]
+pub struct BloomierFilter {
+    seed: u64,
+    value_bits: usize,
+    check_bits: usize,
+    segment_len: usize,
+    slots: Box<[u64]>,
+}
+
+/// Upper bound on the number of seeds tried when constructing a [BloomierFilter].
+const MAX_ATTEMPTS: u64 = 64;
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl BloomierFilter {
+    /// Construct a filter that maps each of the given keys to its value.
+    ///
+    /// Values are stored with `value_bits` bits each. `check_bits` additional bits are
+    /// stored per slot for detecting keys that weren't part of the construction.
+    /// Both together must not exceed 64 bits.
+    ///
+    /// Duplicate keys are fine as long as they map to the same value.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::bloomier::BloomierFilter;
+    ///
+    /// let pairs = (0u32..1000).map(|i| (i.to_le_bytes(), (i % 16) as u64));
+    /// let filter = BloomierFilter::from_pairs(pairs, 4, 0).unwrap();
+    ///
+    /// assert_eq!(filter.get(&17u32.to_le_bytes()), Some(1));
+    /// // Without check bits, absent keys are never detected
+    /// assert!(filter.get(&1001u32.to_le_bytes()).is_some());
+    /// ```
+    pub fn from_pairs<T: AsRef<[u8]>>(
+        pairs: impl IntoIterator<Item = (T, u64)>,
+        value_bits: usize,
+        check_bits: usize,
+    ) -> Result<Self, Error> {
+        let slot_bits = value_bits + check_bits;
+        if slot_bits > 64 {
+            return Err(Error::SlotWidthTooLarge { bits: slot_bits });
+        }
+
+        let mut map = BTreeMap::<Vec<u8>, u64>::new();
+        for (key, value) in pairs {
+            if value & !mask(value_bits) != 0 {
+                return Err(Error::ValueOutOfRange {
+                    value,
+                    bits: value_bits,
+                });
+            }
+
+            let existing = *map.entry(key.as_ref().to_vec()).or_insert(value);
+            if existing != value {
+                return Err(Error::ConflictingValues {
+                    first: existing,
+                    second: value,
+                });
+            }
+        }
+
+        let capacity = 32 + (map.len() as f64 * 1.23).ceil() as usize;
+        let segment_len = (capacity + 2) / 3;
+
+        for seed in 0..MAX_ATTEMPTS {
+            let mut filter = Self {
+                seed,
+                value_bits,
+                check_bits,
+                segment_len,
+                slots: vec![0u64; segment_len * 3].into_boxed_slice(),
+            };
+
+            if filter.try_assign(&map) {
+                return Ok(filter);
+            }
+        }
+
+        Err(Error::ConstructionFailed {
+            attempts: MAX_ATTEMPTS as usize,
+        })
+    }
+
+    /// Look up the value stored for given key.
+    ///
+    /// Returns `None` if the check bits don't match, in which case the key
+    /// definitely wasn't part of the construction. Keys that were part of it
+    /// always return their value.
+    pub fn get(&self, key: &impl AsRef<[u8]>) -> Option<u64> {
+        let stored = self.lookup(key);
+        let check = stored.checked_shr(self.value_bits as u32).unwrap_or(0);
+        if check == self.check_value(key) {
+            Some(stored & mask(self.value_bits))
+        } else {
+            None
+        }
+    }
+
+    /// Look up the value stored for given key without looking at the check bits.
+    ///
+    /// This returns an arbitrary value for keys that weren't part of the construction.
+    pub fn get_unchecked(&self, key: &impl AsRef<[u8]>) -> u64 {
+        self.lookup(key) & mask(self.value_bits)
+    }
+
+    /// Returns the number of bits used for storing each value.
+    pub fn value_bits(&self) -> usize {
+        self.value_bits
+    }
+
+    /// Returns the number of bits used for detecting absent keys.
+    pub fn check_bits(&self) -> usize {
+        self.check_bits
+    }
+
+    /// Returns the number of slots in this filter. Each slot is `value_bits + check_bits` wide.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Return the slot indices that a given key maps to.
+    pub fn hash_indices(&self, key: &impl AsRef<[u8]>) -> [usize; 3] {
+        let hash = xxh3::xxh3_64_with_seed(key.as_ref(), self.seed);
+        [
+            reduce(hash as u32, self.segment_len),
+            reduce(hash.rotate_left(21) as u32, self.segment_len) + self.segment_len,
+            reduce(hash.rotate_left(42) as u32, self.segment_len) + 2 * self.segment_len,
+        ]
+    }
+
+    fn lookup(&self, key: &impl AsRef<[u8]>) -> u64 {
+        self.hash_indices(key)
+            .iter()
+            .fold(0, |acc, &i| acc ^ self.slots[i])
+    }
+
+    fn check_value(&self, key: &impl AsRef<[u8]>) -> u64 {
+        xxh3::xxh3_64_with_seed(key.as_ref(), !self.seed) & mask(self.check_bits)
+    }
+
+    /// Tries to find an assignment of slots for the current seed by peeling
+    /// the 3-hypergraph of keys. Returns false if the hypergraph isn't peelable.
+    fn try_assign(&mut self, map: &BTreeMap<Vec<u8>, u64>) -> bool {
+        let keys = map.iter().collect::<Vec<_>>();
+        let edges = keys
+            .iter()
+            .map(|(key, _)| self.hash_indices(key))
+            .collect::<Vec<_>>();
+
+        let mut counts = vec![0usize; self.slots.len()];
+        let mut xor_keys = vec![0usize; self.slots.len()];
+        for (key_index, edge) in edges.iter().enumerate() {
+            for &slot in edge {
+                counts[slot] += 1;
+                xor_keys[slot] ^= key_index;
+            }
+        }
+
+        let mut queue = (0..self.slots.len())
+            .filter(|&slot| counts[slot] == 1)
+            .collect::<Vec<_>>();
+        let mut stack = Vec::with_capacity(keys.len());
+
+        while let Some(slot) = queue.pop() {
+            if counts[slot] != 1 {
+                continue;
+            }
+
+            let key_index = xor_keys[slot];
+            stack.push((key_index, slot));
+            for &other in &edges[key_index] {
+                counts[other] -= 1;
+                xor_keys[other] ^= key_index;
+                if counts[other] == 1 {
+                    queue.push(other);
+                }
+            }
+        }
+
+        if stack.len() != keys.len() {
+            return false;
+        }
+
+        for &(key_index, slot) in stack.iter().rev() {
+            let (key, value) = keys[key_index];
+            let check = self
+                .check_value(key)
+                .checked_shl(self.value_bits as u32)
+                .unwrap_or(0);
+            let target = check | value;
+            let others = edges[key_index]
+                .iter()
+                .filter(|&&other| other != slot)
+                .fold(0, |acc, &other| acc ^ self.slots[other]);
+            self.slots[slot] = target ^ others;
+        }
+
+        true
+    }
+}
+
+impl Debug for BloomierFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes = self
+            .slots
+            .iter()
+            .flat_map(|slot| slot.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        f.debug_struct("BloomierFilter")
+            .field("seed", &self.seed)
+            .field("value_bits", &self.value_bits)
+            .field("check_bits", &self.check_bits)
+            .field("slots", &HexFieldDebug(bytes))
+            .finish()
+    }
+}
+
+/// Maps a 32-bit hash uniformly into `0..n` without a division.
+fn reduce(hash: u32, n: usize) -> usize {
+    ((hash as u64 * n as u64) >> 32) as usize
+}
+
+/// Returns a mask with the lowest `bits` bits set.
+fn mask(bits: usize) -> u64 {
+    if bits == 0 {
+        0
+    } else {
+        u64::MAX >> (64 - bits)
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_filter_detects_absent_keys() {
+        let filter = BloomierFilter::from_pairs(Vec::<(&[u8], u64)>::new(), 8, 16).unwrap();
+        assert_eq!(filter.get(b"anything"), None);
+    }
+
+    #[test]
+    fn construction_is_order_independent() {
+        let pairs = (0u32..500).map(|i| (i.to_le_bytes(), (i % 7) as u64));
+        let forward = BloomierFilter::from_pairs(pairs.clone(), 3, 4).unwrap();
+        let backward = BloomierFilter::from_pairs(pairs.rev(), 3, 4).unwrap();
+        assert_eq!(forward, backward);
+    }
+
+    #[test]
+    fn conflicting_values_are_rejected() {
+        let result = BloomierFilter::from_pairs([(b"key", 1), (b"key", 2)], 2, 0);
+        assert!(matches!(result, Err(Error::ConflictingValues { .. })));
+    }
+
+    #[test]
+    fn values_must_fit_value_bits() {
+        let result = BloomierFilter::from_pairs([(b"key", 4)], 2, 0);
+        assert!(matches!(result, Err(Error::ValueOutOfRange { .. })));
+    }
+
+    #[test]
+    fn slot_width_is_limited() {
+        let result = BloomierFilter::from_pairs([(b"key", 0)], 60, 5);
+        assert!(matches!(result, Err(Error::SlotWidthTooLarge { bits: 65 })));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::BloomierFilter;
+    use proptest::{collection::btree_map, prop_assert_eq};
+    use test_strategy::proptest;
+
+    #[proptest(cases = 200)]
+    fn inserted_keys_retrieve_their_values(
+        #[strategy(btree_map(proptest::num::u64::ANY, 0u64..256, 0..500))]
+        pairs: std::collections::BTreeMap<u64, u64>,
+    ) {
+        let filter =
+            BloomierFilter::from_pairs(pairs.iter().map(|(k, v)| (k.to_le_bytes(), *v)), 8, 8)
+                .unwrap();
+
+        for (key, value) in pairs.iter() {
+            prop_assert_eq!(filter.get(&key.to_le_bytes()), Some(*value));
+        }
+    }
+}
@@ -30,7 +30,8 @@ pub struct HashIndexIterator<'a, T: AsRef<[u8]>> {
 /// To construct this, use
 /// - [`BloomParams::new_from_fpr`] for constructing this from a given false positive rate and desired capacity,
 /// - similarly [`BloomParams::new_from_fpr_po2`], but with power-of-two sizes,
-/// - [`BloomParams::new_from_size`] for constructing from desired size and capacity.
+/// - [`BloomParams::new_from_size`] for constructing from desired size and capacity,
+/// - [`BloomParams::new_from_fpr_compressed`] for filters that are sent around compressed.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
 pub struct BloomParams {
     /// size of the bloom filter in bytes, non-zero
@@ -52,6 +53,230 @@ pub enum Error {
         /// The actual size of the [Vec].
         actual: usize,
     },
+
+    /// Report that two filters that are supposed to be combined have different parameters.
+    #[error("Bloom filter parameters don't match: expected {expected:?}, but got {actual:?}")]
+    #[diagnostic(url(docsrs))]
+    BloomParamsMismatch {
+        /// The parameters that were expected.
+        expected: BloomParams,
+
+        /// The parameters of the filter that was given.
+        actual: BloomParams,
+    },
+
+    /// Report that two layered filters that are supposed to be combined have a different number of layers.
+    #[error("Filter depths don't match: expected {expected}, but got {actual}")]
+    #[diagnostic(url(docsrs))]
+    DepthMismatch {
+        /// The number of layers that was expected.
+        expected: usize,
+
+        /// The number of layers of the filter that was given.
+        actual: usize,
+    },
+
+    /// Report a number of hash functions that doesn't fit into an encoding.
+    #[error(
+        "Can't encode {k_hashes} hash functions, at most {} are supported",
+        u32::MAX
+    )]
+    #[diagnostic(url(docsrs))]
+    HashCountTooLarge {
+        /// The number of hash functions of the filter.
+        k_hashes: usize,
+    },
+
+    /// Report a Golomb-coded set whose hash range `n_elems * m` doesn't fit into 64 bits.
+    #[error("Hash range of {n_elems} items with false positive rate 1/{m} exceeds 64 bits")]
+    #[diagnostic(url(docsrs))]
+    HashRangeTooLarge {
+        /// The number of distinct items in the set.
+        n_elems: u64,
+
+        /// The inverse false positive rate of the set.
+        m: u64,
+    },
+
+    /// Report that an encoded filter would decompress to more bytes than allowed.
+    #[error("Decompressed size of {byte_size} bytes exceeds the limit of {limit} bytes")]
+    #[diagnostic(url(docsrs))]
+    DecompressedSizeTooLarge {
+        /// The byte size the encoding claims.
+        byte_size: u64,
+
+        /// The maximum byte size that was allowed.
+        limit: usize,
+    },
+
+    /// Report that a layered filter was constructed without any layers.
+    #[error("Layered filters need at least one layer")]
+    #[diagnostic(url(docsrs))]
+    ZeroDepth,
+
+    /// Report that an encoded filter couldn't be decoded.
+    #[error("Malformed filter encoding: {reason}")]
+    #[diagnostic(url(docsrs))]
+    MalformedEncoding {
+        /// What exactly is wrong with the encoding.
+        reason: &'static str,
+    },
+
+    /// Report an unsupported sketch precision.
+    #[error("Unsupported sketch precision {precision}")]
+    #[diagnostic(url(docsrs))]
+    InvalidPrecision {
+        /// The precision that was requested.
+        precision: u8,
+    },
+
+    /// Report that two sketches that are supposed to be merged have different precisions.
+    #[error("Sketch precisions don't match: expected {expected}, but got {actual}")]
+    #[diagnostic(url(docsrs))]
+    PrecisionMismatch {
+        /// The precision that was expected.
+        expected: u8,
+
+        /// The precision of the sketch that was given.
+        actual: u8,
+    },
+
+    /// Report that two signatures that are supposed to be combined have a different number of slots.
+    #[error("Signature sizes don't match: expected {expected}, but got {actual}")]
+    #[diagnostic(url(docsrs))]
+    SignatureSizeMismatch {
+        /// The number of slots that was expected.
+        expected: usize,
+
+        /// The number of slots of the signature that was given.
+        actual: usize,
+    },
+
+    /// Report that a value doesn't fit into the number of bits reserved for it.
+    #[error("Value {value} doesn't fit into {bits} bits")]
+    #[diagnostic(url(docsrs))]
+    ValueOutOfRange {
+        /// The value that was supposed to be stored.
+        value: u64,
+
+        /// The number of bits available for storing it.
+        bits: usize,
+    },
+
+    /// Report that the requested slot width exceeds the supported 64 bits.
+    #[error("Slot width of {bits} bits exceeds the maximum of 64 bits")]
+    #[diagnostic(url(docsrs))]
+    SlotWidthTooLarge {
+        /// The requested slot width in bits.
+        bits: usize,
+    },
+
+    /// Report that the same key was given with two different values.
+    #[error("Key was given with conflicting values {first} and {second}")]
+    #[diagnostic(url(docsrs))]
+    ConflictingValues {
+        /// The value that was given first.
+        first: u64,
+
+        /// The value that was given afterwards.
+        second: u64,
+    },
+
+    /// Report that constructing a static filter didn't succeed.
+    #[error("Failed to construct filter after {attempts} attempts")]
+    #[diagnostic(url(docsrs))]
+    ConstructionFailed {
+        /// The number of seeds that were tried.
+        attempts: usize,
+    },
+
+    /// Report that an item couldn't be encoded into a filter, because all of its slots
+    /// were already taken by other items.
+    #[error("Failed to construct filter: all slots of an item are occupied")]
+    #[diagnostic(url(docsrs))]
+    SlotsExhausted,
+
+    /// Report that bytes don't start with the container format's magic number.
+    #[error("Not a filter container: invalid magic number")]
+    #[diagnostic(url(docsrs))]
+    InvalidMagic,
+
+    /// Report a container format version this version of the crate can't read.
+    #[error("Unsupported container format version {version}")]
+    #[diagnostic(url(docsrs))]
+    UnsupportedVersion {
+        /// The format version of the container.
+        version: u8,
+    },
+
+    /// Report a hashing scheme this version of the crate doesn't implement.
+    #[error("Unsupported hash scheme {scheme} with seed {seed}")]
+    #[diagnostic(url(docsrs))]
+    UnsupportedHashScheme {
+        /// The hash scheme identifier of the container.
+        scheme: u8,
+
+        /// The hash seed of the container.
+        seed: u64,
+    },
+
+    /// Report that a container holds a different kind of filter than requested.
+    #[error("Filter kinds don't match: expected {expected}, but got {actual}")]
+    #[diagnostic(url(docsrs))]
+    FilterKindMismatch {
+        /// The filter kind identifier that was expected.
+        expected: u8,
+
+        /// The filter kind identifier of the container.
+        actual: u8,
+    },
+
+    /// Report that a filter can't be folded by given factor.
+    #[error("Can't fold {byte_size} bytes by a factor of {factor}: both must be powers of two, and the factor at most the byte size")]
+    #[diagnostic(url(docsrs))]
+    InvalidFoldFactor {
+        /// The requested fold factor.
+        factor: usize,
+
+        /// The byte size of the filter.
+        byte_size: usize,
+    },
+
+    /// Report a chunk size that doesn't evenly divide a filter into chunks.
+    #[error("Chunk size {chunk_size} doesn't evenly divide {byte_size} bytes")]
+    #[diagnostic(url(docsrs))]
+    InvalidChunkSize {
+        /// The requested chunk size in bytes.
+        chunk_size: usize,
+
+        /// The byte size of the filter.
+        byte_size: usize,
+    },
+
+    /// Report that a Merkle proof doesn't match the root it's checked against.
+    #[error("Invalid proof: {reason}")]
+    #[diagnostic(url(docsrs))]
+    InvalidProof {
+        /// Why the proof was rejected.
+        reason: &'static str,
+    },
+
+    /// Report that reading or writing a file-backed filter failed.
+    #[error("I/O error: {source}")]
+    #[diagnostic(url(docsrs))]
+    Io {
+        /// The underlying I/O error.
+        #[from]
+        source: std::io::Error,
+    },
+
+    /// Report that a string couldn't be decoded as multibase.
+    #[error("Invalid multibase string: {reason}")]
+    #[diagnostic(url(docsrs))]
+    InvalidMultibase {
+        /// Why decoding failed.
+        reason: String,
+    },
 }
 
 //------------------------------------------------------------------------------
@@ -151,6 +376,59 @@ impl BloomParams {
         }
     }
 
+    /// Construct bloom parameters for given capacity `n_elems` and false positive rate that
+    /// minimize the size of the filter once it's [compressed](crate::compressed::compress),
+    /// while keeping its uncompressed size at or below `max_byte_size`.
+    ///
+    /// `n_elems` must be non-zero, and `fpr` must be between 0.0 and 1.0, exclusive.
+    ///
+    /// As shown for [compressed bloom filters], using fewer hash functions with a larger,
+    /// sparser filter achieves the same false positive rate with a smaller compressed size.
+    /// If even the parameters from [`BloomParams::new_from_fpr`] exceed `max_byte_size`,
+    /// those are returned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::common::BloomParams;
+    ///
+    /// let optimal = BloomParams::new_from_fpr(1_000, 0.001);
+    /// let params = BloomParams::new_from_fpr_compressed(1_000, 0.001, 4 * optimal.byte_size);
+    ///
+    /// assert!(params.k_hashes < optimal.k_hashes);
+    /// assert!(params.byte_size <= 4 * optimal.byte_size);
+    /// assert!(params.false_positive_rate_at(1_000) <= 0.001);
+    /// ```
+    ///
+    /// [compressed bloom filters]: https://doi.org/10.1109/TNET.2002.803864
+    pub fn new_from_fpr_compressed(n_elems: u64, fpr: f64, max_byte_size: usize) -> Self {
+        let uncompressed = Self::new_from_fpr(n_elems, fpr);
+        let n = n_elems as f64;
+
+        (1..=uncompressed.k_hashes)
+            .filter_map(|k_hashes| {
+                // Solve (1 - e^(-kn/m))^k = fpr for m.
+                let k = k_hashes as f64;
+                let zero_fraction = 1.0 - fpr.powf(1.0 / k);
+                let bit_size = -k * n / zero_fraction.ln();
+                let byte_size = (bit_size / 8.0).ceil() as usize;
+                if !bit_size.is_finite() || byte_size > max_byte_size {
+                    return None;
+                }
+
+                let params = Self {
+                    byte_size,
+                    k_hashes,
+                };
+                // Compressed size is close to the entropy of the bit array.
+                let p = zero_fraction;
+                let entropy = -(p * p.log2() + (1.0 - p) * (1.0 - p).log2());
+                Some((bit_size * entropy, params))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .map_or(uncompressed, |(_, params)| params)
+    }
+
     /// Construct optimal bloom parameters for given bloom filter `byte_size` and capacity `n_elems`.
     pub fn new_from_size(byte_size: usize, n_elems: u64) -> Self {
         Self {
@@ -0,0 +1,326 @@
+use crate::{
+    common::Error,
+    utils::{read_varint, write_varint, BitReader, BitWriter},
+};
+use bitvec::{prelude::Lsb0, view::BitView};
+use std::f64::consts::LN_2;
+
+//------------------------------------------------------------------------------
+// Constants
+//------------------------------------------------------------------------------
+
+/// Marks an encoding that stores the bit array as-is.
+const MODE_RAW: u8 = 0;
+
+/// Marks an encoding that stores Golomb-Rice coded gaps between set bits.
+const MODE_RICE: u8 = 1;
+
+/// How many times larger than its encoding a bit array [`decompress`] returns may be.
+pub const MAX_EXPANSION: usize = 1 << 16;
+
+/// The size in bytes up to which [`decompress`] returns bit arrays regardless of [`MAX_EXPANSION`].
+const MIN_LIMIT: usize = 1 << 20;
+
+//------------------------------------------------------------------------------
+// Functions
+//------------------------------------------------------------------------------
+
+/// Compress a bloom filter's bit array for transport, following the idea of
+/// [compressed bloom filters].
+///
+/// The gaps between set bits are [Golomb-Rice coded], with the Rice parameter
+/// picked from the filter's fill ratio. Filters that are far from half full compress
+/// well this way. If compression doesn't make the encoding smaller, the bytes are
+/// stored uncompressed instead, so the encoding is at most a couple of bytes larger
+/// than the input.
+///
+/// The output is deterministic and can be turned back into the exact same
+/// bit array with [`decompress`].
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{compressed, runtime_size::BloomFilter};
+///
+/// let mut filter = BloomFilter::new_from_fpr(10_000, 0.001);
+/// for i in 0u32..100 {
+///     filter.insert(&i.to_le_bytes());
+/// }
+///
+/// let compressed = compressed::compress(filter.as_bytes());
+/// assert!(compressed.len() < filter.as_bytes().len() / 10);
+/// assert_eq!(compressed::decompress(&compressed).unwrap(), filter.as_bytes());
+/// ```
+///
+/// [compressed bloom filters]: https://doi.org/10.1109/TNET.2002.803864
+/// [Golomb-Rice coded]: https://en.wikipedia.org/wiki/Golomb_coding#Rice_coding
+pub fn compress(bytes: &[u8]) -> Vec<u8> {
+    let bits = bytes.view_bits::<Lsb0>();
+    let count = bits.count_ones();
+    let rice_bits = rice_parameter(bits.len(), count);
+
+    let mut writer = BitWriter::default();
+    let mut next = 0;
+    for index in bits.iter_ones() {
+        writer.write_rice((index - next) as u64, rice_bits);
+        next = index + 1;
+    }
+    let gaps = writer.finish();
+
+    let mut header = Vec::new();
+    write_varint(&mut header, bytes.len() as u64);
+
+    let mut encoded = Vec::new();
+    if gaps.len() + varint_len(count as u64) + 1 < bytes.len() {
+        encoded.push(MODE_RICE);
+        encoded.extend_from_slice(&header);
+        write_varint(&mut encoded, count as u64);
+        encoded.push(rice_bits as u8);
+        encoded.extend_from_slice(&gaps);
+    } else {
+        encoded.push(MODE_RAW);
+        encoded.extend_from_slice(&header);
+        encoded.extend_from_slice(bytes);
+    }
+
+    encoded
+}
+
+/// Decompress a bit array that was compressed with [`compress`].
+///
+/// To protect against tiny inputs that claim to decompress to huge bit arrays, this
+/// refuses to decompress to more than [`MAX_EXPANSION`] times the size of the input,
+/// or 1 MiB, whichever is larger. Very sparse filters can compress even better than
+/// that, use [`decompress_with_limit`] to decompress them.
+///
+/// Fails if the input isn't exactly an encoding produced by [`compress`].
+pub fn decompress(encoded: &[u8]) -> Result<Vec<u8>, Error> {
+    let limit = encoded.len().saturating_mul(MAX_EXPANSION).max(MIN_LIMIT);
+    decompress_with_limit(encoded, limit)
+}
+
+/// Decompress a bit array that was compressed with [`compress`], if it's at most
+/// `max_byte_size` bytes long.
+///
+/// Fails if the input isn't exactly an encoding produced by [`compress`].
+pub fn decompress_with_limit(encoded: &[u8], max_byte_size: usize) -> Result<Vec<u8>, Error> {
+    let malformed = |reason| Error::MalformedEncoding { reason };
+
+    let (&mode, rest) = encoded
+        .split_first()
+        .ok_or_else(|| malformed("missing encoding mode"))?;
+    let mut offset = 0;
+    let byte_size = read_varint(rest, &mut offset).ok_or_else(|| malformed("invalid byte size"))?;
+    if offset != varint_len(byte_size) {
+        return Err(malformed("non-canonical byte size"));
+    }
+    let byte_size = usize::try_from(byte_size)
+        .ok()
+        .filter(|&byte_size| byte_size <= max_byte_size)
+        .ok_or(Error::DecompressedSizeTooLarge {
+            byte_size,
+            limit: max_byte_size,
+        })?;
+
+    match mode {
+        MODE_RAW => {
+            let raw = &rest[offset..];
+            if raw.len() != byte_size {
+                return Err(Error::VectorImportSizeMismatch {
+                    expected: byte_size,
+                    actual: raw.len(),
+                });
+            }
+            if compress(raw)[0] != MODE_RAW {
+                return Err(malformed("compressible bit array stored raw"));
+            }
+            Ok(raw.to_vec())
+        }
+        MODE_RICE => {
+            let bit_size = byte_size
+                .checked_mul(8)
+                .ok_or_else(|| malformed("byte size too large"))?;
+            let count_offset = offset;
+            let count = read_varint(rest, &mut offset)
+                .filter(|&count| count as usize <= bit_size)
+                .ok_or_else(|| malformed("invalid set bit count"))?;
+            if offset - count_offset != varint_len(count) {
+                return Err(malformed("non-canonical set bit count"));
+            }
+            let count = count as usize;
+            let rice_bits = *rest
+                .get(offset)
+                .ok_or_else(|| malformed("invalid rice parameter"))?;
+            if rice_bits as u32 != rice_parameter(bit_size, count) {
+                return Err(malformed("non-canonical rice parameter"));
+            }
+
+            // Every gap takes at least `rice_bits + 1` bits, so the claimed count has to
+            // fit into the input, and the compressed encoding has to be the smaller one.
+            let gaps = &rest[offset + 1..];
+            let min_gap_bits = (count as u128) * (rice_bits as u128 + 1);
+            if min_gap_bits > gaps.len() as u128 * 8 {
+                return Err(malformed("truncated gap"));
+            }
+            if gaps.len() + varint_len(count as u64) + 1 >= byte_size {
+                return Err(malformed("incompressible bit array stored compressed"));
+            }
+
+            let mut bytes = vec![0u8; byte_size];
+            let mut reader = BitReader::new(gaps);
+            let mut next = 0usize;
+            for _ in 0..count {
+                let gap = reader
+                    .read_rice(rice_bits as u32)
+                    .ok_or_else(|| malformed("truncated gap"))?;
+                let index = usize::try_from(gap)
+                    .ok()
+                    .and_then(|gap| next.checked_add(gap))
+                    .filter(|&index| index < bit_size)
+                    .ok_or_else(|| malformed("set bit out of range"))?;
+                bytes.view_bits_mut::<Lsb0>().set(index, true);
+                next = index + 1;
+            }
+
+            if !reader.only_padding_left() {
+                return Err(malformed("trailing data"));
+            }
+
+            Ok(bytes)
+        }
+        _ => Err(malformed("unknown encoding mode")),
+    }
+}
+
+/// Returns the number of bytes `value` takes up as varint.
+fn varint_len(value: u64) -> usize {
+    let mut bytes = Vec::new();
+    write_varint(&mut bytes, value);
+    bytes.len()
+}
+
+/// Picks the Rice parameter for coding the gaps between `count` set bits among `bit_size` bits.
+pub(crate) fn rice_parameter(bit_size: usize, count: usize) -> u32 {
+    if count == 0 {
+        return 0;
+    }
+
+    // Gaps are roughly geometrically distributed, for which this is close to optimal.
+    let mean_gap = (bit_size - count) as f64 / count as f64;
+    let rice_bits = (mean_gap * LN_2).log2().floor();
+    if rice_bits.is_finite() && rice_bits > 0.0 {
+        (rice_bits as u32).min(63)
+    } else {
+        0
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_full_filters_are_stored_raw() {
+        let bytes = [0xAAu8; 100];
+        let encoded = compress(&bytes);
+        assert_eq!(encoded[0], MODE_RAW);
+        assert_eq!(decompress(&encoded).unwrap(), bytes);
+    }
+
+    #[test]
+    fn trailing_data_is_rejected() {
+        let mut encoded = compress(&[0u8; 1000]);
+        encoded.push(0);
+        assert!(matches!(
+            decompress(&encoded),
+            Err(Error::MalformedEncoding { .. })
+        ));
+    }
+
+    #[test]
+    fn huge_sizes_are_rejected_before_allocating() {
+        let mut encoded = vec![MODE_RICE];
+        write_varint(&mut encoded, 1 << 40);
+        write_varint(&mut encoded, 0);
+        encoded.push(rice_parameter(1 << 43, 0) as u8);
+        assert!(matches!(
+            decompress(&encoded),
+            Err(Error::DecompressedSizeTooLarge { .. })
+        ));
+
+        let encoded = compress(&vec![0u8; 1 << 21]);
+        assert!(decompress(&encoded).is_err());
+        assert_eq!(
+            decompress_with_limit(&encoded, 1 << 21).unwrap(),
+            vec![0u8; 1 << 21]
+        );
+    }
+
+    #[test]
+    fn non_canonical_encodings_are_rejected() {
+        let mut bytes = [0u8; 1000];
+        bytes[17] = 0x11;
+        let encoded = compress(&bytes);
+        assert_eq!(encoded[0], MODE_RICE);
+
+        let mut other_rice_bits = encoded.clone();
+        let rice_offset = 1 + varint_len(1000) + varint_len(2);
+        other_rice_bits[rice_offset] += 1;
+        assert!(decompress(&other_rice_bits).is_err());
+
+        let mut raw = vec![MODE_RAW];
+        write_varint(&mut raw, bytes.len() as u64);
+        raw.extend_from_slice(&bytes);
+        assert!(decompress(&raw).is_err());
+
+        let mut too_many = encoded[..rice_offset - 1].to_vec();
+        write_varint(&mut too_many, 100);
+        too_many.push(rice_parameter(8000, 100) as u8);
+        assert!(decompress(&too_many).is_err());
+    }
+
+    #[test]
+    fn overflowing_varints_are_rejected() {
+        let mut max = Vec::new();
+        write_varint(&mut max, u64::MAX);
+        assert_eq!(max.len(), 10);
+        assert_eq!(read_varint(&max, &mut 0), Some(u64::MAX));
+
+        let mut overflowing = max;
+        overflowing[9] = 0x02;
+        assert_eq!(read_varint(&overflowing, &mut 0), None);
+    }
+
+    #[test]
+    fn empty_input_is_rejected() {
+        assert!(decompress(&[]).is_err());
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::{compress, decompress};
+    use proptest::{collection::vec, prop_assert, prop_assert_eq};
+    use test_strategy::proptest;
+
+    #[proptest]
+    fn round_trips(#[strategy(vec(proptest::num::u8::ANY, 0..300))] bytes: Vec<u8>) {
+        prop_assert_eq!(decompress(&compress(&bytes)).unwrap(), bytes);
+    }
+
+    #[proptest]
+    fn sparse_round_trips(#[strategy(vec(0usize..8000, 0..100))] ones: Vec<usize>) {
+        let mut bytes = vec![0u8; 1000];
+        for one in ones {
+            bytes[one / 8] |= 1 << (one % 8);
+        }
+
+        let encoded = compress(&bytes);
+        prop_assert!(encoded.len() <= bytes.len() + 3);
+        prop_assert_eq!(decompress(&encoded).unwrap(), bytes);
+    }
+}
@@ -1,8 +1,11 @@
 use crate::{
-    common::{Error, HashIndexIterator},
+    common::{BloomParams, Error, HashIndexIterator},
+    container::{Container, KIND_CONST_SIZE},
+    privacy::PrivatizedBloomFilter,
     utils::{ByteArrayVisitor, HexFieldDebug},
 };
 use bitvec::prelude::BitArray;
+use rand_core::RngCore;
 use serde::{Deserialize, Serialize};
 use std::{fmt::Debug, ops::Index};
 
@@ -26,7 +29,8 @@ use std::{fmt::Debug, ops::Index};
 ///
 /// assert!(filter.contains(&[0xF5u8; 32]));
 /// ```
-#[derive(Clone, PartialEq, Eq, PartialOrd)]
+#[derive(Clone, Eq, PartialOrd)]
+#[cfg_attr(not(feature = "constant_time"), derive(PartialEq))]
 pub struct BloomFilter<const N: usize, const K: usize> {
     /// The underlying `BitArray`
     pub bits: BitArray<[u8; N]>,
@@ -110,6 +114,36 @@ impl<const N: usize, const K: usize> BloomFilter<N, K> {
         self.hash_indices(item).all(|i| self.bits[i])
     }
 
+    /// Checks if the item is in the bloom filter, in constant time.
+    ///
+    /// Unlike [`contains`](BloomFilter::contains), this always reads all `K` bits of
+    /// the item, and combines them without branching, so the time it takes doesn't
+    /// depend on which of them are set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use deterministic_bloom::const_size::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::<256, 30>::default();
+    /// filter.insert(&[0xF5u8; 32]);
+    ///
+    /// assert!(bool::from(filter.ct_contains(&[0xF5u8; 32])));
+    /// ```
+    #[cfg(feature = "constant_time")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "constant_time")))]
+    pub fn ct_contains<T>(&self, item: &T) -> subtle::Choice
+    where
+        T: AsRef<[u8]>,
+    {
+        let bytes = self.as_bytes();
+        let mut found = subtle::Choice::from(1);
+        for i in self.hash_indices(item) {
+            found &= subtle::Choice::from((bytes[i / 8] >> (i % 8)) & 1);
+        }
+        found
+    }
+
     /// Counts the number of bits set in the bloom filter.
     ///
     /// # Examples
@@ -164,6 +198,189 @@ impl<const N: usize, const K: usize> BloomFilter<N, K> {
     pub fn as_bytes(&self) -> &[u8] {
         self.bits.as_raw_slice()
     }
+
+    /// Get the bytes of the bloom filter [compressed](crate::compressed::compress) for transport.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use deterministic_bloom::const_size::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::<256, 30>::default();
+    /// filter.insert(&[0xF5u8; 32]);
+    ///
+    /// let compressed = filter.to_compressed_bytes();
+    /// assert!(compressed.len() < 256);
+    ///
+    /// let decompressed = BloomFilter::<256, 30>::from_compressed_bytes(&compressed).unwrap();
+    /// assert_eq!(decompressed, filter);
+    /// ```
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        crate::compressed::compress(self.as_bytes())
+    }
+
+    /// Construct the bloom filter from its [compressed](crate::compressed::compress) bytes.
+    ///
+    /// Fails if the encoding is malformed or if the decompressed size isn't `N`.
+    pub fn from_compressed_bytes(compressed: &[u8]) -> Result<Self, Error> {
+        Self::try_from(crate::compressed::decompress_with_limit(compressed, N)?)
+    }
+
+    /// Encode this filter into the canonical, self-describing container format for archiving.
+    ///
+    /// Fails if the number of hash functions doesn't fit into 32 bits.
+    ///
+    /// The layout is the same as for [`runtime_size::BloomFilter::to_container_bytes`](crate::runtime_size::BloomFilter::to_container_bytes),
+    /// with filter kind [`KIND_CONST_SIZE`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use deterministic_bloom::const_size::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::<256, 30>::default();
+    /// filter.insert(&[0xF5u8; 32]);
+    ///
+    /// let bytes = filter.to_container_bytes().unwrap();
+    /// assert_eq!(BloomFilter::<256, 30>::from_container_bytes(&bytes).unwrap(), filter);
+    /// assert!(BloomFilter::<256, 20>::from_container_bytes(&bytes).is_err());
+    /// ```
+    pub fn to_container_bytes(&self) -> Result<Vec<u8>, Error> {
+        Container::encode(KIND_CONST_SIZE, K, self.as_bytes())
+    }
+
+    /// Decode a filter from its [container](BloomFilter::to_container_bytes) encoding.
+    ///
+    /// Decoding is strict: Fails if any header field is invalid or unsupported, if the
+    /// container holds a different kind of filter, or if its parameters aren't `N` and `K`.
+    pub fn from_container_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let container = Container::decode(KIND_CONST_SIZE, bytes)?;
+        if container.k_hashes != K || container.payload.len() != N {
+            return Err(Error::BloomParamsMismatch {
+                expected: BloomParams {
+                    byte_size: N,
+                    k_hashes: K,
+                },
+                actual: BloomParams {
+                    byte_size: container.payload.len(),
+                    k_hashes: container.k_hashes,
+                },
+            });
+        }
+
+        Self::try_from(container.payload.to_vec())
+    }
+
+    /// Returns the canonical IPLD representation of this filter, see [`SCHEMA`](crate::ipld::SCHEMA).
+    #[cfg(feature = "ipld")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+    pub fn to_ipld(&self) -> libipld::Ipld {
+        crate::ipld::to_ipld(K, self.as_bytes())
+    }
+
+    /// Parse a filter from its canonical IPLD representation, see [`SCHEMA`](crate::ipld::SCHEMA).
+    ///
+    /// Fails if the representation doesn't match the schema or its parameters aren't `N` and `K`.
+    #[cfg(feature = "ipld")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+    pub fn from_ipld(ipld: &libipld::Ipld) -> Result<Self, Error> {
+        let (k_hashes, bytes) = crate::ipld::from_ipld(ipld)?;
+        if k_hashes != K || bytes.len() != N {
+            return Err(Error::BloomParamsMismatch {
+                expected: BloomParams {
+                    byte_size: N,
+                    k_hashes: K,
+                },
+                actual: BloomParams {
+                    byte_size: bytes.len(),
+                    k_hashes,
+                },
+            });
+        }
+
+        Self::try_from(bytes.to_vec())
+    }
+
+    /// Compute the CIDv1 of this filter's canonical DAG-CBOR encoding, hashed with given multihash.
+    ///
+    /// Every producer computes the same CID for filters with the same parameters and bits.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use deterministic_bloom::const_size::BloomFilter;
+    /// use libipld::multihash::Code;
+    ///
+    /// let mut filter = BloomFilter::<256, 30>::default();
+    /// filter.insert(b"Hello");
+    ///
+    /// let cid = filter.cid(Code::Sha2_256);
+    /// assert_eq!(cid.codec(), 0x71); // DAG-CBOR
+    /// ```
+    #[cfg(feature = "ipld")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+    pub fn cid(&self, code: libipld::multihash::Code) -> libipld::Cid {
+        crate::ipld::cid(K, self.as_bytes(), code)
+    }
+
+    /// Encode this filter's [container](BloomFilter::to_container_bytes) as multibase string
+    /// in given base, e.g. for JSON APIs, query strings or HTTP headers.
+    ///
+    /// Use [`FromStr`](std::str::FromStr) to decode it again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use deterministic_bloom::const_size::BloomFilter;
+    /// use multibase::Base;
+    ///
+    /// let mut filter = BloomFilter::<256, 30>::default();
+    /// filter.insert(&[0xF5u8; 32]);
+    ///
+    /// let string = filter.to_multibase_string(Base::Base58Btc).unwrap();
+    /// assert_eq!(string.parse::<BloomFilter<256, 30>>().unwrap(), filter);
+    /// assert!(string.parse::<BloomFilter<128, 30>>().is_err());
+    /// ```
+    #[cfg(feature = "multibase")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "multibase")))]
+    pub fn to_multibase_string(&self, base: multibase::Base) -> Result<String, Error> {
+        Ok(multibase::encode(base, self.to_container_bytes()?))
+    }
+
+    /// Release a noised copy of this filter with `epsilon`-differential privacy.
+    ///
+    /// Each bit is flipped independently with probability `1 / (1 + e^(epsilon / K))`.
+    /// `epsilon` must be positive. See [`PrivatizedBloomFilter`] for how to query the result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use deterministic_bloom::const_size::BloomFilter;
+    /// use rand::{rngs::StdRng, SeedableRng};
+    ///
+    /// let mut filter = BloomFilter::<256, 4>::default();
+    /// filter.insert(&[0xF5u8; 32]);
+    ///
+    /// let private = filter.privatize(16.0, &mut StdRng::seed_from_u64(0));
+    /// assert!(private.likely_contains(&[0xF5u8; 32]));
+    /// ```
+    pub fn privatize(&self, epsilon: f64, rng: &mut impl RngCore) -> PrivatizedBloomFilter {
+        PrivatizedBloomFilter::privatize(K, self.as_bytes(), epsilon, rng)
+    }
+}
+
+/// Parses the [multibase string](BloomFilter::to_multibase_string) of a filter in any base.
+#[cfg(feature = "multibase")]
+#[cfg_attr(docsrs, doc(cfg(feature = "multibase")))]
+impl<const N: usize, const K: usize> std::str::FromStr for BloomFilter<N, K> {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, bytes) = multibase::decode(s).map_err(|e| Error::InvalidMultibase {
+            reason: e.to_string(),
+        })?;
+        Self::from_container_bytes(&bytes)
+    }
 }
 
 impl<const N: usize, const K: usize> TryFrom<Vec<u8>> for BloomFilter<N, K> {
@@ -196,6 +413,34 @@ impl<const N: usize, const K: usize> Default for BloomFilter<N, K> {
     }
 }
 
+#[cfg(feature = "ipld")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+impl<const N: usize, const K: usize> libipld::codec::Encode<libipld::cbor::DagCborCodec>
+    for BloomFilter<N, K>
+{
+    fn encode<W: std::io::Write>(
+        &self,
+        c: libipld::cbor::DagCborCodec,
+        w: &mut W,
+    ) -> libipld::Result<()> {
+        self.to_ipld().encode(c, w)
+    }
+}
+
+#[cfg(feature = "ipld")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+impl<const N: usize, const K: usize> libipld::codec::Decode<libipld::cbor::DagCborCodec>
+    for BloomFilter<N, K>
+{
+    fn decode<R: std::io::Read + std::io::Seek>(
+        c: libipld::cbor::DagCborCodec,
+        r: &mut R,
+    ) -> libipld::Result<Self> {
+        let ipld: libipld::Ipld = libipld::codec::Decode::decode(c, r)?;
+        Ok(Self::from_ipld(&ipld)?)
+    }
+}
+
 impl<const N: usize, const K: usize> Serialize for BloomFilter<N, K> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -222,6 +467,38 @@ impl<const N: usize, const K: usize> AsRef<[u8]> for &BloomFilter<N, K> {
     }
 }
 
+#[cfg(feature = "constant_time")]
+#[cfg_attr(docsrs, doc(cfg(feature = "constant_time")))]
+impl<const N: usize, const K: usize> subtle::ConstantTimeEq for BloomFilter<N, K> {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.as_bytes().ct_eq(other.as_bytes())
+    }
+}
+
+/// With the `constant_time` feature, `==` compares in constant time via [`ct_eq`](subtle::ConstantTimeEq::ct_eq).
+#[cfg(feature = "constant_time")]
+#[cfg_attr(docsrs, doc(cfg(feature = "constant_time")))]
+impl<const N: usize, const K: usize> PartialEq for BloomFilter<N, K> {
+    fn eq(&self, other: &Self) -> bool {
+        subtle::ConstantTimeEq::ct_eq(self, other).into()
+    }
+}
+
+#[cfg(feature = "constant_time")]
+#[cfg_attr(docsrs, doc(cfg(feature = "constant_time")))]
+impl<const N: usize, const K: usize> zeroize::Zeroize for BloomFilter<N, K> {
+    fn zeroize(&mut self) {
+        self.bits.as_raw_mut_slice().zeroize();
+    }
+}
+
+#[cfg(feature = "constant_time")]
+impl<const N: usize, const K: usize> Drop for BloomFilter<N, K> {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
 impl<const N: usize, const K: usize> Debug for BloomFilter<N, K> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("BloomFilter")
@@ -255,6 +532,44 @@ mod tests {
         assert!(!bloom.contains(b"tird"));
     }
 
+    #[cfg(feature = "constant_time")]
+    #[test]
+    fn constant_time_operations_agree() {
+        use subtle::ConstantTimeEq;
+
+        let mut bloom = BloomFilter::<256, 30>::new();
+        bloom.insert(b"first");
+
+        assert!(bool::from(bloom.ct_eq(&bloom.clone())));
+        assert!(!bool::from(bloom.ct_eq(&BloomFilter::new())));
+        assert!(bloom == bloom.clone() && bloom != BloomFilter::new());
+        for item in [b"first", b"other"] {
+            assert_eq!(bool::from(bloom.ct_contains(item)), bloom.contains(item));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "multibase")]
+    fn multibase_strings_are_validated() {
+        let filter = BloomFilter::<256, 30>::new();
+        let string = filter
+            .to_multibase_string(multibase::Base::Base64Url)
+            .unwrap();
+
+        assert!(matches!(
+            string.parse::<BloomFilter<256, 20>>(),
+            Err(Error::BloomParamsMismatch { .. })
+        ));
+        assert!(matches!(
+            "not multibase".parse::<BloomFilter<256, 30>>(),
+            Err(Error::InvalidMultibase { .. })
+        ));
+        assert!(matches!(
+            string.parse::<crate::runtime_size::BloomFilter>(),
+            Err(Error::FilterKindMismatch { .. })
+        ));
+    }
+
     #[test]
     fn serialized_bloom_filter_can_be_deserialized_correctly() {
         let mut bloom = BloomFilter::<256, 30>::new();
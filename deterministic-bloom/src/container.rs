@@ -0,0 +1,217 @@
+use crate::common::Error;
+
+//------------------------------------------------------------------------------
+// Constants
+//------------------------------------------------------------------------------
+
+/// The magic number every container starts with.
+pub const MAGIC: [u8; 4] = *b"DBLF";
+
+/// The container format version written by this version of the crate.
+pub const VERSION: u8 = 1;
+
+/// Filter kind identifier for [`runtime_size::BloomFilter`](crate::runtime_size::BloomFilter).
+pub const KIND_RUNTIME_SIZE: u8 = 0;
+
+/// Filter kind identifier for [`const_size::BloomFilter`](crate::const_size::BloomFilter).
+pub const KIND_CONST_SIZE: u8 = 1;
+
+/// Hash scheme identifier for the scheme implemented by [`HashIndexIterator`](crate::common::HashIndexIterator):
+/// The `i`-th hash index is 64-bit xxh3 with seed `seed + i`, reduced modulo the next
+/// power of two of the bit length, with rejection sampling.
+pub const HASH_SCHEME_XXH3: u8 = 0;
+
+/// The length of the container header preceding the payload.
+pub const HEADER_LEN: usize = 4 + 1 + 1 + 1 + 8 + 4 + 8;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// The parsed header and payload of a filter container.
+///
+/// See [`runtime_size::BloomFilter::to_container_bytes`](crate::runtime_size::BloomFilter::to_container_bytes)
+/// for the layout.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Container<'a> {
+    pub(crate) k_hashes: usize,
+    pub(crate) payload: &'a [u8],
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl<'a> Container<'a> {
+    /// Encode a filter of given kind into a container.
+    ///
+    /// Fails if `k_hashes` doesn't fit into 32 bits.
+    pub(crate) fn encode(kind: u8, k_hashes: usize, payload: &[u8]) -> Result<Vec<u8>, Error> {
+        let mut bytes = Self::encode_header(kind, k_hashes, payload.len())?;
+        bytes.extend_from_slice(payload);
+        Ok(bytes)
+    }
+
+    /// Encode only the header of a container for a payload of `byte_size` bytes.
+    ///
+    /// Fails if `k_hashes` doesn't fit into 32 bits.
+    pub(crate) fn encode_header(
+        kind: u8,
+        k_hashes: usize,
+        byte_size: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let k_hashes =
+            u32::try_from(k_hashes).map_err(|_| Error::HashCountTooLarge { k_hashes })?;
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.push(VERSION);
+        bytes.push(kind);
+        bytes.push(HASH_SCHEME_XXH3);
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&k_hashes.to_le_bytes());
+        bytes.extend_from_slice(&(byte_size as u64 * 8).to_le_bytes());
+        Ok(bytes)
+    }
+
+    /// Decode a container, checking that it holds a filter of the expected kind.
+    pub(crate) fn decode(expected_kind: u8, bytes: &'a [u8]) -> Result<Self, Error> {
+        let malformed = |reason| Error::MalformedEncoding { reason };
+        if bytes.len() < HEADER_LEN {
+            return Err(if bytes.starts_with(&MAGIC) {
+                malformed("truncated container header")
+            } else {
+                Error::InvalidMagic
+            });
+        }
+
+        let (header, payload) = bytes.split_at(HEADER_LEN);
+        if header[..4] != MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+
+        let version = header[4];
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion { version });
+        }
+
+        let kind = header[5];
+        if kind != expected_kind {
+            return Err(Error::FilterKindMismatch {
+                expected: expected_kind,
+                actual: kind,
+            });
+        }
+
+        let scheme = header[6];
+        let seed = u64::from_le_bytes(header[7..15].try_into().expect("8 bytes"));
+        if scheme != HASH_SCHEME_XXH3 || seed != 0 {
+            return Err(Error::UnsupportedHashScheme { scheme, seed });
+        }
+
+        let k_hashes = u32::from_le_bytes(header[15..19].try_into().expect("4 bytes"));
+        if k_hashes == 0 {
+            return Err(malformed("k_hashes must be non-zero"));
+        }
+
+        let bit_len = u64::from_le_bytes(header[19..27].try_into().expect("8 bytes"));
+        if bit_len % 8 != 0 {
+            return Err(malformed("bit length is not a multiple of 8"));
+        }
+        let byte_len = usize::try_from(bit_len / 8)
+            .map_err(|_| malformed("bit length exceeds addressable memory"))?;
+        if payload.len() != byte_len {
+            return Err(Error::VectorImportSizeMismatch {
+                expected: byte_len,
+                actual: payload.len(),
+            });
+        }
+
+        Ok(Self {
+            k_hashes: k_hashes as usize,
+            payload,
+        })
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded() -> Vec<u8> {
+        Container::encode(KIND_RUNTIME_SIZE, 3, &[0xAB; 16]).unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        let bytes = encoded();
+        let container = Container::decode(KIND_RUNTIME_SIZE, &bytes).unwrap();
+        assert_eq!(container.k_hashes, 3);
+        assert_eq!(container.payload, &[0xAB; 16]);
+    }
+
+    #[test]
+    fn header_fields_are_validated() {
+        let mut bytes = encoded();
+        bytes[0] = b'X';
+        assert!(matches!(
+            Container::decode(KIND_RUNTIME_SIZE, &bytes),
+            Err(Error::InvalidMagic)
+        ));
+
+        let mut bytes = encoded();
+        bytes[4] = VERSION + 1;
+        assert!(matches!(
+            Container::decode(KIND_RUNTIME_SIZE, &bytes),
+            Err(Error::UnsupportedVersion { .. })
+        ));
+
+        assert!(matches!(
+            Container::decode(KIND_CONST_SIZE, &encoded()),
+            Err(Error::FilterKindMismatch { .. })
+        ));
+
+        let mut bytes = encoded();
+        bytes[7] = 1;
+        assert!(matches!(
+            Container::decode(KIND_RUNTIME_SIZE, &bytes),
+            Err(Error::UnsupportedHashScheme { .. })
+        ));
+
+        let mut bytes = encoded();
+        bytes[15..19].copy_from_slice(&0u32.to_le_bytes());
+        assert!(matches!(
+            Container::decode(KIND_RUNTIME_SIZE, &bytes),
+            Err(Error::MalformedEncoding { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn huge_hash_counts_are_rejected() {
+        assert!(matches!(
+            Container::encode(KIND_RUNTIME_SIZE, u32::MAX as usize + 1, &[]),
+            Err(Error::HashCountTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn payload_length_is_validated() {
+        let mut bytes = encoded();
+        bytes.push(0);
+        assert!(matches!(
+            Container::decode(KIND_RUNTIME_SIZE, &bytes),
+            Err(Error::VectorImportSizeMismatch { .. })
+        ));
+
+        let bytes = encoded();
+        assert!(matches!(
+            Container::decode(KIND_RUNTIME_SIZE, &bytes[..HEADER_LEN - 1]),
+            Err(Error::MalformedEncoding { .. })
+        ));
+    }
+}
@@ -0,0 +1,370 @@
+use crate::{common::Error, const_size, runtime_size};
+use serde::{de::Error as _, Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt::Debug};
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// Bloom filters that can be replicated as the state of a [`GrowOnlyFilter`].
+///
+/// The join of two filters is the union of their bits, so it's commutative,
+/// associative and idempotent.
+pub trait JoinableFilter: Clone + PartialEq {
+    /// Insert an item into the filter.
+    fn insert_item(&mut self, item: &[u8]);
+
+    /// Check whether an item was (probably) inserted into the filter.
+    fn contains_item(&self, item: &[u8]) -> bool;
+
+    /// Join `other` into this filter, by setting all bits that are set in `other`.
+    ///
+    /// Fails if the filters have different parameters.
+    fn join_with(&mut self, other: &Self) -> Result<(), Error>;
+}
+
+/// A grow-only, state-based [CRDT] of a bloom filter.
+///
+/// Each replica inserts items into its own copy and sends its whole state to other
+/// replicas from time to time, which [`join`](GrowOnlyFilter::join) it into theirs.
+/// Replicas that received the same inserts end up in the same state, no matter in
+/// which order, how often or via which other replicas they received them.
+///
+/// Next to the filter, the state tracks how many inserts each replica made. Since
+/// every replica only increments its own counter, joining takes the maximum of each
+/// counter, so the sum of all counters is the total number of inserts all replicas
+/// made, even though the same inserts reach a replica over many paths.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{crdt::GrowOnlyFilter, runtime_size::BloomFilter};
+///
+/// let mut alice = GrowOnlyFilter::new(1, BloomFilter::new_from_fpr(1_000, 0.001));
+/// let mut bob = GrowOnlyFilter::new(2, BloomFilter::new_from_fpr(1_000, 0.001));
+/// alice.insert(b"Hello");
+/// bob.insert(b"World!");
+///
+/// alice.join(&bob).unwrap();
+/// bob.join(&alice).unwrap();
+///
+/// assert!(alice.contains(b"World!") && bob.contains(b"Hello"));
+/// assert_eq!(alice.filter(), bob.filter());
+/// assert_eq!(alice.insert_count(), 2);
+/// ```
+///
+/// [CRDT]: https://en.wikipedia.org/wiki/Conflict-free_replicated_data_type
+#[derive(Clone, PartialEq, Eq)]
+pub struct GrowOnlyFilter<F> {
+    replica: u64,
+    filter: F,
+    inserts: BTreeMap<u64, u64>,
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl<F: JoinableFilter> GrowOnlyFilter<F> {
+    /// Start replicating `filter` on the replica with given id.
+    ///
+    /// Every replica needs a unique id, and all replicas need to start out with the same
+    /// empty filter.
+    pub fn new(replica: u64, filter: F) -> Self {
+        Self {
+            replica,
+            filter,
+            inserts: BTreeMap::new(),
+        }
+    }
+
+    /// Insert an item on this replica.
+    pub fn insert(&mut self, item: &impl AsRef<[u8]>) {
+        self.filter.insert_item(item.as_ref());
+        *self.inserts.entry(self.replica).or_insert(0) += 1;
+    }
+
+    /// Check whether an item was (probably) inserted on any replica whose state was joined
+    /// into this one.
+    pub fn contains(&self, item: &impl AsRef<[u8]>) -> bool {
+        self.filter.contains_item(item.as_ref())
+    }
+
+    /// Join the state of another replica into this one.
+    ///
+    /// Fails if the filters have different parameters, in which case this state is left
+    /// unchanged.
+    pub fn join(&mut self, other: &Self) -> Result<(), Error> {
+        self.filter.join_with(&other.filter)?;
+        for (&replica, &count) in other.inserts.iter() {
+            let entry = self.inserts.entry(replica).or_insert(0);
+            *entry = (*entry).max(count);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the id of this replica.
+    pub fn replica(&self) -> u64 {
+        self.replica
+    }
+
+    /// Returns the replicated filter.
+    pub fn filter(&self) -> &F {
+        &self.filter
+    }
+
+    /// Returns the number of inserts made on given replica that are known to this one.
+    pub fn inserts_by(&self, replica: u64) -> u64 {
+        self.inserts.get(&replica).copied().unwrap_or(0)
+    }
+
+    /// Returns the total number of inserts known to this replica.
+    ///
+    /// Inserting the same item several times counts each time, so this is an upper bound
+    /// of the number of distinct items in the filter.
+    pub fn insert_count(&self) -> u64 {
+        self.inserts.values().sum()
+    }
+
+    /// Returns the insert counters of all replicas known to this one, ordered by replica id.
+    pub fn counters(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.inserts
+            .iter()
+            .map(|(&replica, &count)| (replica, count))
+    }
+}
+
+impl JoinableFilter for runtime_size::BloomFilter {
+    fn insert_item(&mut self, item: &[u8]) {
+        self.insert(&item);
+    }
+
+    fn contains_item(&self, item: &[u8]) -> bool {
+        self.contains(&item)
+    }
+
+    fn join_with(&mut self, other: &Self) -> Result<(), Error> {
+        self.union_with(other)
+    }
+}
+
+impl<const N: usize, const K: usize> JoinableFilter for const_size::BloomFilter<N, K> {
+    fn insert_item(&mut self, item: &[u8]) {
+        self.insert(&item);
+    }
+
+    fn contains_item(&self, item: &[u8]) -> bool {
+        self.contains(&item)
+    }
+
+    fn join_with(&mut self, other: &Self) -> Result<(), Error> {
+        self.bits |= other.bits;
+        Ok(())
+    }
+}
+
+/// Serializes as a tuple of the replica id, the filter and a list of pairs of replica
+/// ids and their insert counters, ordered by replica id.
+impl<F: Serialize> Serialize for GrowOnlyFilter<F> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let inserts = self.inserts.iter().collect::<Vec<_>>();
+        (self.replica, &self.filter, inserts).serialize(serializer)
+    }
+}
+
+impl<'de, F: Deserialize<'de>> Deserialize<'de> for GrowOnlyFilter<F> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (replica, filter, pairs) = <(u64, F, Vec<(u64, u64)>)>::deserialize(deserializer)?;
+
+        let mut inserts = BTreeMap::new();
+        for (other, count) in pairs {
+            if inserts
+                .keys()
+                .next_back()
+                .map_or(false, |&last| last >= other)
+            {
+                return Err(D::Error::custom(Error::MalformedEncoding {
+                    reason: "insert counters are not ordered by replica id",
+                }));
+            }
+            inserts.insert(other, count);
+        }
+
+        Ok(Self {
+            replica,
+            filter,
+            inserts,
+        })
+    }
+}
+
+impl<F: Debug> Debug for GrowOnlyFilter<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GrowOnlyFilter")
+            .field("replica", &self.replica)
+            .field("filter", &self.filter)
+            .field("inserts", &self.inserts)
+            .finish()
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counters_are_not_double_counted() {
+        let mut alice = GrowOnlyFilter::new(1, runtime_size::BloomFilter::new_from_fpr(100, 0.01));
+        let mut bob = GrowOnlyFilter::new(2, alice.filter().clone());
+        let mut carol = GrowOnlyFilter::new(3, alice.filter().clone());
+        alice.insert(b"Hello");
+        bob.insert(b"World!");
+
+        carol.join(&alice).unwrap();
+        bob.join(&alice).unwrap();
+        carol.join(&bob).unwrap();
+        carol.join(&bob).unwrap();
+
+        assert_eq!(carol.insert_count(), 2);
+        assert_eq!(carol.counters().collect::<Vec<_>>(), vec![(1, 1), (2, 1)]);
+        assert_eq!(carol.inserts_by(3), 0);
+    }
+
+    #[test]
+    fn mismatching_filters_are_rejected() {
+        let mut a = GrowOnlyFilter::new(1, runtime_size::BloomFilter::new_from_fpr(100, 0.01));
+        let mut b = GrowOnlyFilter::new(2, runtime_size::BloomFilter::new_from_fpr(200, 0.01));
+        b.insert(b"Hello");
+
+        assert!(matches!(a.join(&b), Err(Error::BloomParamsMismatch { .. })));
+        assert_eq!(a.insert_count(), 0);
+    }
+
+    #[test]
+    fn serialized_state_can_be_deserialized_correctly() {
+        let mut state = GrowOnlyFilter::new(7, const_size::BloomFilter::<256, 30>::new());
+        state.insert(b"Hello");
+        let mut other = GrowOnlyFilter::new(3, const_size::BloomFilter::<256, 30>::new());
+        other.insert(b"World!");
+        state.join(&other).unwrap();
+
+        let ipld = libipld::serde::to_ipld(&state).unwrap();
+        let deserialized: GrowOnlyFilter<const_size::BloomFilter<256, 30>> =
+            libipld::serde::from_ipld(ipld).unwrap();
+
+        assert_eq!(deserialized, state);
+    }
+
+    #[test]
+    fn unordered_counters_are_rejected() {
+        let filter = runtime_size::BloomFilter::new_from_fpr(100, 0.01);
+        let ipld = libipld::serde::to_ipld((1u64, &filter, vec![(2u64, 1u64), (1, 1)])).unwrap();
+        assert!(
+            libipld::serde::from_ipld::<GrowOnlyFilter<runtime_size::BloomFilter>>(ipld).is_err()
+        );
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::{GrowOnlyFilter, JoinableFilter};
+    use crate::{const_size, runtime_size};
+    use proptest::{collection::vec, prop_assert_eq};
+    use test_strategy::proptest;
+
+    type Ops = Vec<(u8, u32)>;
+
+    /// Builds the state of replica `replica` after all replicas made the inserts in `ops`
+    /// and sent their states to it.
+    fn replicate<F: JoinableFilter>(empty: &F, replica: u64, ops: &Ops) -> GrowOnlyFilter<F> {
+        let mut replicas = (0..4)
+            .map(|id| GrowOnlyFilter::new(id, empty.clone()))
+            .collect::<Vec<_>>();
+        for (id, item) in ops {
+            replicas[*id as usize % 4].insert(&item.to_le_bytes());
+        }
+
+        let mut state = replicas[replica as usize].clone();
+        for other in replicas.iter() {
+            state.join(other).unwrap();
+        }
+        state
+    }
+
+    fn ops() -> impl proptest::strategy::Strategy<Value = Ops> {
+        vec((proptest::num::u8::ANY, proptest::num::u32::ANY), 0..30)
+    }
+
+    fn assert_laws<F: JoinableFilter + std::fmt::Debug>(
+        empty: F,
+        a: &Ops,
+        b: &Ops,
+        c: &Ops,
+    ) -> Result<(), proptest::test_runner::TestCaseError> {
+        let a = replicate(&empty, 0, a);
+        let b = replicate(&empty, 1, b);
+        let c = replicate(&empty, 2, c);
+        let join = |x: &GrowOnlyFilter<F>, y: &GrowOnlyFilter<F>| {
+            let mut x = x.clone();
+            x.join(y).unwrap();
+            x
+        };
+        let state = |x: GrowOnlyFilter<F>| (x.filter().clone(), x.counters().collect::<Vec<_>>());
+
+        // Commutativity
+        prop_assert_eq!(state(join(&a, &b)), state(join(&b, &a)));
+        // Associativity
+        prop_assert_eq!(
+            state(join(&join(&a, &b), &c)),
+            state(join(&a, &join(&b, &c)))
+        );
+        // Idempotence
+        prop_assert_eq!(join(&a, &a), a);
+        Ok(())
+    }
+
+    #[proptest]
+    fn runtime_size_joins_form_a_semilattice(
+        #[strategy(ops())] a: Ops,
+        #[strategy(ops())] b: Ops,
+        #[strategy(ops())] c: Ops,
+    ) {
+        assert_laws(
+            runtime_size::BloomFilter::new_from_fpr(100, 0.01),
+            &a,
+            &b,
+            &c,
+        )?;
+    }
+
+    #[proptest]
+    fn const_size_joins_form_a_semilattice(
+        #[strategy(ops())] a: Ops,
+        #[strategy(ops())] b: Ops,
+        #[strategy(ops())] c: Ops,
+    ) {
+        assert_laws(const_size::BloomFilter::<64, 5>::new(), &a, &b, &c)?;
+    }
+
+    #[proptest]
+    fn joined_counters_count_all_inserts(#[strategy(ops())] ops: Ops) {
+        let empty = runtime_size::BloomFilter::new_from_fpr(100, 0.01);
+        let state = replicate(&empty, 3, &ops);
+
+        prop_assert_eq!(state.insert_count(), ops.len() as u64);
+        for (_, item) in ops {
+            proptest::prop_assert!(state.contains(&item.to_le_bytes()));
+        }
+    }
+}
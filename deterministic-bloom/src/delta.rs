@@ -0,0 +1,390 @@
+use crate::{
+    common::{BloomParams, Error},
+    compressed::rice_parameter,
+    runtime_size::BloomFilter,
+    utils::{BitReader, BitWriter, DeserializeBytes, HexFieldDebug, SerializeBytes},
+};
+use bitvec::{prelude::Lsb0, view::BitView};
+use serde::{de::Error as _, Deserialize, Serialize};
+use std::fmt::Debug;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// The bits that differ between two versions of a filter, for incremental sync.
+///
+/// The positions of flipped bits are stored as [Golomb-Rice coded] gaps, so a delta
+/// takes a few bits per flipped bit instead of the whole filter. Applying it only
+/// touches the flipped bits.
+///
+/// Since bloom filters usually only grow, most deltas only set bits. These are marked
+/// as grow-only and applied by setting their bits instead of flipping them, which
+/// makes applying them idempotent and order-independent: A replica can apply grow-only
+/// deltas in any order, repeatedly, and on top of any version of the filter.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{delta, runtime_size::BloomFilter};
+///
+/// let mut old = BloomFilter::new_from_fpr(10_000, 0.001);
+/// old.insert(b"Hello");
+///
+/// let mut new = old.clone();
+/// new.insert(b"World!");
+///
+/// let delta = delta::diff(&old, &new).unwrap();
+/// assert!(delta.is_grow_only());
+///
+/// let mut replica = old.clone();
+/// delta::apply(&mut replica, &delta).unwrap();
+/// assert_eq!(replica, new);
+/// ```
+///
+/// [Golomb-Rice coded]: https://en.wikipedia.org/wiki/Golomb_coding#Rice_coding
+#[derive(Clone, PartialEq, Eq)]
+pub struct FilterDelta {
+    k_hashes: usize,
+    byte_size: usize,
+    grow_only: bool,
+    count: u64,
+    rice_bits: u8,
+    gaps: Vec<u8>,
+}
+
+//------------------------------------------------------------------------------
+// Functions
+//------------------------------------------------------------------------------
+
+/// Compute the delta that turns `old` into `new`.
+///
+/// Fails if the filters have different parameters.
+pub fn diff(old: &BloomFilter, new: &BloomFilter) -> Result<FilterDelta, Error> {
+    if old.get_bloom_params() != new.get_bloom_params() {
+        return Err(Error::BloomParamsMismatch {
+            expected: old.get_bloom_params(),
+            actual: new.get_bloom_params(),
+        });
+    }
+
+    let flipped = old
+        .as_bytes()
+        .iter()
+        .zip(new.as_bytes())
+        .map(|(old, new)| old ^ new)
+        .collect::<Vec<_>>();
+    let grow_only = old
+        .as_bytes()
+        .iter()
+        .zip(new.as_bytes())
+        .all(|(old, new)| old & !new == 0);
+
+    let flipped = flipped.view_bits::<Lsb0>();
+    let count = flipped.count_ones();
+    let rice_bits = rice_parameter(flipped.len(), count);
+
+    let mut writer = BitWriter::default();
+    let mut next = 0;
+    for index in flipped.iter_ones() {
+        writer.write_rice((index - next) as u64, rice_bits);
+        next = index + 1;
+    }
+
+    Ok(FilterDelta {
+        k_hashes: old.hash_count(),
+        byte_size: old.as_bytes().len(),
+        grow_only,
+        count: count as u64,
+        rice_bits: rice_bits as u8,
+        gaps: writer.finish(),
+    })
+}
+
+/// Apply a delta computed with [`diff`] to `filter`.
+///
+/// Grow-only deltas set their bits, so they can be applied to any version of the filter.
+/// Other deltas flip their bits, so they need to be applied to exactly the `old` filter
+/// they were computed from.
+///
+/// Fails if the delta was computed for filters with different parameters.
+pub fn apply(filter: &mut BloomFilter, delta: &FilterDelta) -> Result<(), Error> {
+    if filter.get_bloom_params() != delta.bloom_params() {
+        return Err(Error::BloomParamsMismatch {
+            expected: filter.get_bloom_params(),
+            actual: delta.bloom_params(),
+        });
+    }
+
+    let bits = filter.as_bytes_mut().view_bits_mut::<Lsb0>();
+    if delta.grow_only {
+        for index in delta.positions() {
+            bits.set(index, true);
+        }
+    } else {
+        for index in delta.positions() {
+            let bit = bits[index];
+            bits.set(index, !bit);
+        }
+    }
+
+    Ok(())
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl FilterDelta {
+    /// Returns the parameters of the filters this delta applies to.
+    pub fn bloom_params(&self) -> BloomParams {
+        BloomParams {
+            byte_size: self.byte_size,
+            k_hashes: self.k_hashes,
+        }
+    }
+
+    /// Returns true if this delta only sets bits.
+    pub fn is_grow_only(&self) -> bool {
+        self.grow_only
+    }
+
+    /// Returns the number of flipped bits.
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    /// Returns true if both filter versions were the same.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Returns the positions of the flipped bits in ascending order.
+    pub fn positions(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut reader = BitReader::new(&self.gaps);
+        let mut next = 0usize;
+        (0..self.count).map(move |_| {
+            let index = next
+                + reader
+                    .read_rice(self.rice_bits as u32)
+                    .expect("encoding is validated on construction") as usize;
+            next = index + 1;
+            index
+        })
+    }
+
+    /// Checks that the gaps encode exactly `count` positions within the filter.
+    fn validate(&self) -> Result<(), Error> {
+        let malformed = |reason| Error::MalformedEncoding { reason };
+        if self.rice_bits >= 64 {
+            return Err(malformed("invalid rice parameter"));
+        }
+        if self.k_hashes == 0 {
+            return Err(malformed("k_hashes must be non-zero"));
+        }
+
+        let bit_size = self
+            .byte_size
+            .checked_mul(8)
+            .ok_or_else(|| malformed("byte size too large"))?;
+        if self.count > bit_size as u64 {
+            return Err(malformed("invalid flipped bit count"));
+        }
+
+        let mut reader = BitReader::new(&self.gaps);
+        let mut next = 0usize;
+        for _ in 0..self.count {
+            let gap = reader
+                .read_rice(self.rice_bits as u32)
+                .ok_or_else(|| malformed("truncated gap"))?;
+            let index = usize::try_from(gap)
+                .ok()
+                .and_then(|gap| next.checked_add(gap))
+                .filter(|&index| index < bit_size)
+                .ok_or_else(|| malformed("flipped bit out of range"))?;
+            next = index + 1;
+        }
+
+        if !reader.only_padding_left() {
+            return Err(malformed("trailing data"));
+        }
+
+        Ok(())
+    }
+}
+
+/// Serializes as a tuple of `k_hashes`, the byte size, whether the delta is grow-only,
+/// the number of flipped bits, the Rice parameter and the encoded gaps.
+impl Serialize for FilterDelta {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (
+            self.k_hashes as u64,
+            self.byte_size as u64,
+            self.grow_only,
+            self.count,
+            self.rice_bits,
+            SerializeBytes(&self.gaps),
+        )
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FilterDelta {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (k_hashes, byte_size, grow_only, count, rice_bits, DeserializeBytes(gaps)) =
+            <(u64, u64, bool, u64, u8, DeserializeBytes)>::deserialize(deserializer)?;
+
+        let too_large = |reason| D::Error::custom(Error::MalformedEncoding { reason });
+        let delta = FilterDelta {
+            k_hashes: usize::try_from(k_hashes).map_err(|_| too_large("k_hashes too large"))?,
+            byte_size: usize::try_from(byte_size).map_err(|_| too_large("byte size too large"))?,
+            grow_only,
+            count,
+            rice_bits,
+            gaps,
+        };
+        delta.validate().map_err(D::Error::custom)?;
+
+        Ok(delta)
+    }
+}
+
+impl Debug for FilterDelta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FilterDelta")
+            .field("k_hashes", &self.k_hashes)
+            .field("byte_size", &self.byte_size)
+            .field("grow_only", &self.grow_only)
+            .field("count", &self.count)
+            .field("rice_bits", &self.rice_bits)
+            .field("gaps", &HexFieldDebug(&self.gaps))
+            .finish()
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shrinking_deltas_flip_bits() {
+        let mut old = BloomFilter::new_from_fpr(100, 0.001);
+        old.insert(b"Hello");
+        let new = BloomFilter::new_from_fpr(100, 0.001);
+
+        let delta = diff(&old, &new).unwrap();
+        assert!(!delta.is_grow_only());
+        assert_eq!(delta.len(), old.count_ones());
+
+        apply(&mut old, &delta).unwrap();
+        assert_eq!(old, new);
+    }
+
+    #[test]
+    fn serialized_delta_can_be_deserialized_correctly() {
+        let old = BloomFilter::new_from_fpr(100, 0.001);
+        let mut new = old.clone();
+        new.insert(b"Hello");
+        let delta = diff(&old, &new).unwrap();
+
+        let ipld = libipld::serde::to_ipld(&delta).unwrap();
+        let deserialized: FilterDelta = libipld::serde::from_ipld(ipld).unwrap();
+
+        assert_eq!(deserialized, delta);
+    }
+
+    #[test]
+    fn out_of_range_positions_are_rejected() {
+        let old = BloomFilter::new_from_fpr(100, 0.001);
+        let mut new = old.clone();
+        new.insert(b"Hello");
+        let mut delta = diff(&old, &new).unwrap();
+        delta.byte_size = 1;
+
+        let ipld = libipld::serde::to_ipld(&delta).unwrap();
+        assert!(libipld::serde::from_ipld::<FilterDelta>(ipld).is_err());
+    }
+
+    #[test]
+    fn oversized_byte_sizes_are_rejected() {
+        let ipld = libipld::serde::to_ipld((1u64, u64::MAX, true, 0u64, 0u8, SerializeBytes(&[])))
+            .unwrap();
+        let error = libipld::serde::from_ipld::<FilterDelta>(ipld).unwrap_err();
+        assert!(error.to_string().contains("byte size too large"));
+    }
+
+    #[test]
+    fn mismatching_params_are_rejected() {
+        let old = BloomFilter::new_from_fpr(100, 0.001);
+        let new = BloomFilter::new_from_fpr(200, 0.001);
+        assert!(matches!(
+            diff(&old, &new),
+            Err(Error::BloomParamsMismatch { .. })
+        ));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::{apply, diff};
+    use crate::runtime_size::BloomFilter;
+    use proptest::{collection::vec, prop_assert, prop_assert_eq};
+    use test_strategy::proptest;
+
+    #[proptest]
+    fn applying_diff_gives_new_filter(
+        #[strategy(vec(proptest::num::u8::ANY, 64))] old: Vec<u8>,
+        #[strategy(vec(proptest::num::u8::ANY, 64))] new: Vec<u8>,
+    ) {
+        let old = BloomFilter::new_with(3, old.into_boxed_slice());
+        let new = BloomFilter::new_with(3, new.into_boxed_slice());
+
+        let delta = diff(&old, &new).unwrap();
+        let mut patched = old.clone();
+        apply(&mut patched, &delta).unwrap();
+        prop_assert_eq!(patched, new);
+    }
+
+    #[proptest]
+    fn grow_only_deltas_commute(
+        #[strategy(vec(proptest::num::u32::ANY, 0..50))] a: Vec<u32>,
+        #[strategy(vec(proptest::num::u32::ANY, 0..50))] b: Vec<u32>,
+    ) {
+        let base = BloomFilter::new_from_fpr(100, 0.01);
+        let mut with_a = base.clone();
+        let mut with_b = base.clone();
+        for item in a.iter() {
+            with_a.insert(&item.to_le_bytes());
+        }
+        for item in b.iter() {
+            with_b.insert(&item.to_le_bytes());
+        }
+
+        let delta_a = diff(&base, &with_a).unwrap();
+        let delta_b = diff(&base, &with_b).unwrap();
+        prop_assert!(delta_a.is_grow_only() && delta_b.is_grow_only());
+
+        let mut ab = base.clone();
+        apply(&mut ab, &delta_a).unwrap();
+        apply(&mut ab, &delta_b).unwrap();
+        let mut ba = base;
+        apply(&mut ba, &delta_b).unwrap();
+        apply(&mut ba, &delta_a).unwrap();
+        apply(&mut ba, &delta_a).unwrap();
+
+        let mut union = with_a;
+        union.union_with(&with_b).unwrap();
+        prop_assert_eq!(&ab, &union);
+        prop_assert_eq!(&ba, &union);
+    }
+}
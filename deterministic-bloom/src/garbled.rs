@@ -0,0 +1,339 @@
+use crate::{
+    common::{BloomParams, Error, HashIndexIterator},
+    runtime_size::BloomFilter,
+    utils::HexFieldDebug,
+};
+use bitvec::{prelude::Lsb0, view::BitView};
+use rand_core::RngCore;
+use std::{collections::BTreeSet, fmt::Debug};
+use xxhash_rust::xxh3;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A [garbled bloom filter] as introduced by Dong, Chen and Wen for private set intersection.
+///
+/// Instead of bits, each of its slots holds a `share_bytes`-byte share. An item is
+/// contained iff the shares in its `k` slots XOR to the item's value, which is a
+/// `share_bytes`-byte digest of the item. All slots that aren't needed to encode some
+/// item are filled with randomness, so that, unlike with a bloom filter, looking at
+/// the slots alone doesn't reveal which slots are "set".
+///
+/// In the PSI protocol, the receiver obtains, via oblivious transfer, those slots of the
+/// sender's garbled filter that are set in the receiver's own bloom filter, and random
+/// values for all other slots. [`intersect_with`](GarbledBloomFilter::intersect_with)
+/// computes that result locally, which is useful for testing protocol implementations.
+///
+/// Note that item values are derived with xxh3, which is not a cryptographic hash
+/// function. Pre-hash items with a cryptographic hash function if they can be chosen
+/// by an adversary.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{common::BloomParams, garbled::GarbledBloomFilter, runtime_size::BloomFilter};
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let mut rng = StdRng::seed_from_u64(42);
+/// let params = BloomParams::new_from_fpr(100, 1.0 / 1_000_000.0);
+///
+/// // The sender garbles its set
+/// let sender_items = ["apple", "banana", "cherry"];
+/// let garbled = GarbledBloomFilter::new(&sender_items, &params, 16, &mut rng).unwrap();
+///
+/// // The receiver has a regular bloom filter with the same parameters
+/// let receiver_items = ["banana", "cherry", "durian"];
+/// let mut filter = BloomFilter::new_from_fpr(100, 1.0 / 1_000_000.0);
+/// for item in receiver_items.iter() {
+///     filter.insert(item);
+/// }
+///
+/// let intersection = garbled.intersect_with(&filter, &mut rng).unwrap();
+/// assert_eq!(intersection.intersection(&receiver_items), vec![&"banana", &"cherry"]);
+/// ```
+///
+/// [garbled bloom filter]: https://doi.org/10.1145/2508859.2516701
+#[derive(Clone, PartialEq, Eq)]
+pub struct GarbledBloomFilter {
+    k_hashes: usize,
+    share_bytes: usize,
+    slots: Box<[u8]>,
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl GarbledBloomFilter {
+    /// Construct a garbled bloom filter from all of its items.
+    ///
+    /// The filter has one slot per bit of a bloom filter with the given parameters,
+    /// and each slot holds a `share_bytes`-byte share. `share_bytes` determines the
+    /// security parameter and the false positive rate, which is `2^(-8 * share_bytes)`.
+    ///
+    /// Given the same items and an RNG in the same state, construction is deterministic.
+    /// Item order and duplicate items don't matter.
+    ///
+    /// Fails with [`Error::SlotsExhausted`] if some item can't be encoded, because all
+    /// of its slots are taken by other items already. With parameters sized for the number of items this
+    /// is very unlikely.
+    pub fn new<T: AsRef<[u8]>>(
+        items: impl IntoIterator<Item = T>,
+        params: &BloomParams,
+        share_bytes: usize,
+        rng: &mut impl RngCore,
+    ) -> Result<Self, Error> {
+        let items = items
+            .into_iter()
+            .map(|item| item.as_ref().to_vec())
+            .collect::<BTreeSet<_>>();
+
+        let slot_count = params.byte_size * 8;
+        let mut filter = Self {
+            k_hashes: params.k_hashes,
+            share_bytes,
+            slots: vec![0u8; slot_count * share_bytes].into_boxed_slice(),
+        };
+        let mut occupied = vec![false; slot_count];
+
+        for item in items.iter() {
+            let indices = filter.hash_indices(item);
+            let free = indices
+                .iter()
+                .position(|&i| !occupied[i])
+                .ok_or(Error::SlotsExhausted)?;
+
+            let mut share = filter.item_value(item);
+            for (position, &i) in indices.iter().enumerate() {
+                if position == free {
+                    continue;
+                }
+
+                if !occupied[i] {
+                    rng.fill_bytes(filter.slot_mut(i));
+                    occupied[i] = true;
+                }
+                xor_into(&mut share, filter.slot(i));
+            }
+
+            let free = indices[free];
+            filter.slot_mut(free).copy_from_slice(&share);
+            occupied[free] = true;
+        }
+
+        for (i, _) in occupied.iter().enumerate().filter(|(_, taken)| !**taken) {
+            rng.fill_bytes(filter.slot_mut(i));
+        }
+
+        Ok(filter)
+    }
+
+    /// Check whether an item is part of the set.
+    ///
+    /// False positives happen with probability `2^(-8 * share_bytes)`.
+    pub fn contains(&self, item: &impl AsRef<[u8]>) -> bool {
+        if self.slot_count() == 0 {
+            return false;
+        }
+
+        let mut value = vec![0u8; self.share_bytes];
+        for i in self.hash_indices(item) {
+            xor_into(&mut value, self.slot(i));
+        }
+
+        value == self.item_value(item)
+    }
+
+    /// Returns all items of `items` that are part of the set, in their original order.
+    pub fn intersection<'a, T: AsRef<[u8]>>(&self, items: &'a [T]) -> Vec<&'a T> {
+        items.iter().filter(|item| self.contains(item)).collect()
+    }
+
+    /// Compute the garbled filter that a PSI receiver with given bloom filter ends up with:
+    /// The slots for bits set in `filter` are copied from this filter, all other slots
+    /// are replaced with randomness.
+    ///
+    /// The result contains exactly those items of this filter that are also in `filter`
+    /// (except for false positives).
+    ///
+    /// Fails if `filter` doesn't have one bit per slot of this filter, or uses a
+    /// different number of hash functions.
+    pub fn intersect_with(
+        &self,
+        filter: &BloomFilter,
+        rng: &mut impl RngCore,
+    ) -> Result<Self, Error> {
+        let expected = self.bloom_params();
+        let actual = filter.get_bloom_params();
+        if expected != actual {
+            return Err(Error::BloomParamsMismatch { expected, actual });
+        }
+
+        let mut intersection = self.clone();
+        for (i, bit) in filter.as_bytes().view_bits::<Lsb0>().iter().enumerate() {
+            if !*bit {
+                rng.fill_bytes(intersection.slot_mut(i));
+            }
+        }
+
+        Ok(intersection)
+    }
+
+    /// Returns the number of slots.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len().checked_div(self.share_bytes).unwrap_or(0)
+    }
+
+    /// Returns how many slots each item is spread over.
+    pub fn hash_count(&self) -> usize {
+        self.k_hashes
+    }
+
+    /// Returns the size of each share in bytes.
+    pub fn share_bytes(&self) -> usize {
+        self.share_bytes
+    }
+
+    /// Returns the bloom parameters of filters that are compatible with this garbled filter.
+    pub fn bloom_params(&self) -> BloomParams {
+        BloomParams {
+            byte_size: self.slot_count() / 8,
+            k_hashes: self.k_hashes,
+        }
+    }
+
+    /// Return the distinct slot indices for given item.
+    ///
+    /// These are the indices a [`BloomFilter`] with compatible parameters would set
+    /// for the item, without duplicates. Duplicate indices would cancel out when
+    /// XOR-ing shares, so they're only used once.
+    pub fn hash_indices(&self, item: &impl AsRef<[u8]>) -> Vec<usize> {
+        let mut indices = Vec::with_capacity(self.k_hashes);
+        for i in HashIndexIterator::new(item, self.slot_count()).take(self.k_hashes) {
+            if !indices.contains(&i) {
+                indices.push(i);
+            }
+        }
+        indices
+    }
+
+    /// Derives the value the item's shares XOR to.
+    fn item_value(&self, item: &impl AsRef<[u8]>) -> Vec<u8> {
+        // Use seeds that bloom filter hash indices don't use in practice.
+        (0u64..)
+            .flat_map(|block| {
+                xxh3::xxh3_128_with_seed(item.as_ref(), u64::MAX - block).to_le_bytes()
+            })
+            .take(self.share_bytes)
+            .collect()
+    }
+
+    fn slot(&self, index: usize) -> &[u8] {
+        &self.slots[index * self.share_bytes..(index + 1) * self.share_bytes]
+    }
+
+    fn slot_mut(&mut self, index: usize) -> &mut [u8] {
+        &mut self.slots[index * self.share_bytes..(index + 1) * self.share_bytes]
+    }
+}
+
+impl Debug for GarbledBloomFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GarbledBloomFilter")
+            .field("k_hashes", &self.k_hashes)
+            .field("share_bytes", &self.share_bytes)
+            .field("slots", &HexFieldDebug(&self.slots))
+            .finish()
+    }
+}
+
+fn xor_into(target: &mut [u8], source: &[u8]) {
+    for (t, s) in target.iter_mut().zip(source) {
+        *t ^= s;
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn construction_is_deterministic() {
+        let params = BloomParams::new_from_fpr(10, 0.001);
+        let a = GarbledBloomFilter::new(["a", "b"], &params, 16, &mut StdRng::seed_from_u64(1));
+        let b =
+            GarbledBloomFilter::new(["b", "a", "b"], &params, 16, &mut StdRng::seed_from_u64(1));
+        assert_eq!(a.unwrap(), b.unwrap());
+    }
+
+    #[test]
+    fn exhausted_slots_are_reported() {
+        let params = BloomParams {
+            byte_size: 1,
+            k_hashes: 1,
+        };
+        let items = (0u32..9).map(|i| i.to_le_bytes());
+        assert!(matches!(
+            GarbledBloomFilter::new(items, &params, 16, &mut StdRng::seed_from_u64(1)),
+            Err(Error::SlotsExhausted)
+        ));
+    }
+
+    #[test]
+    fn mismatching_filters_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let params = BloomParams::new_from_fpr(10, 0.001);
+        let garbled = GarbledBloomFilter::new(["a"], &params, 16, &mut rng).unwrap();
+        let filter = BloomFilter::new_from_fpr(20, 0.001);
+        assert!(matches!(
+            garbled.intersect_with(&filter, &mut rng),
+            Err(Error::BloomParamsMismatch { .. })
+        ));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::GarbledBloomFilter;
+    use crate::{common::BloomParams, runtime_size::BloomFilter};
+    use proptest::{collection::btree_set, prop_assert, prop_assert_eq};
+    use rand::{rngs::StdRng, SeedableRng};
+    use test_strategy::proptest;
+
+    #[proptest(cases = 50)]
+    fn intersection_is_exact(
+        #[strategy(btree_set(0u32..500, 1..100))] sender: std::collections::BTreeSet<u32>,
+        #[strategy(btree_set(0u32..500, 1..100))] receiver: std::collections::BTreeSet<u32>,
+        seed: u64,
+    ) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let params = BloomParams::new_from_fpr(100, 0.0001);
+
+        let sender_items = sender.iter().map(|i| i.to_le_bytes()).collect::<Vec<_>>();
+        let garbled = GarbledBloomFilter::new(&sender_items, &params, 16, &mut rng).unwrap();
+        for item in sender_items.iter() {
+            prop_assert!(garbled.contains(item));
+        }
+
+        let mut filter = BloomFilter::new_from_fpr(100, 0.0001);
+        let receiver_items = receiver.iter().map(|i| i.to_le_bytes()).collect::<Vec<_>>();
+        for item in receiver_items.iter() {
+            filter.insert(item);
+        }
+
+        let intersection = garbled.intersect_with(&filter, &mut rng).unwrap();
+        let expected = sender.intersection(&receiver).collect::<Vec<_>>();
+        let actual = intersection
+            .intersection(&receiver_items)
+            .into_iter()
+            .map(|bytes| u32::from_le_bytes(*bytes))
+            .collect::<Vec<_>>();
+        prop_assert_eq!(actual.iter().collect::<Vec<_>>(), expected);
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{
+    common::Error,
+    utils::{BitReader, BitWriter, HexFieldDebug},
+};
+use std::{collections::BTreeSet, fmt::Debug};
+use xxhash_rust::xxh3;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A static [Golomb-coded set], as used for compact block filters in [BIP158].
+///
+/// Each of the `N` items is hashed uniformly into the range `[0, N·M)`. The sorted
+/// hashes are then stored as Golomb-Rice coded differences, which takes roughly
+/// `log2(M) + 2` bits per item for a false positive rate of `1/M`. That's smaller
+/// than a bloom filter with the same false positive rate, but the set can't be
+/// modified after construction and queries need to decode the set from the start.
+///
+/// Like the bloom filters in this crate, items only need to be representable as bytes
+/// and hashing is deterministic, so the same items always produce the same set.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::gcs::GolombCodedSet;
+///
+/// let items = (0u32..1000).map(|i| i.to_le_bytes());
+/// let set = GolombCodedSet::new_from_fpr(items, 1.0 / 1_000_000.0).unwrap();
+///
+/// assert!(set.contains(&10u32.to_le_bytes()));
+/// assert!(!set.contains(&1001u32.to_le_bytes())); // true in all but 1 in a million cases
+/// assert!(set.as_bytes().len() < 1000 * 3);
+/// ```
+///
+/// [Golomb-coded set]: https://en.wikipedia.org/wiki/Golomb_coding#Use_for_compressing_sets
+/// [BIP158]: https://github.com/bitcoin/bips/blob/master/bip-0158.mediawiki
+#[derive(Clone, PartialEq, Eq)]
+pub struct GolombCodedSet {
+    n_elems: u64,
+    rice_bits: u8,
+    m: u64,
+    bytes: Box<[u8]>,
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl GolombCodedSet {
+    /// Construct a set from all of its items, with false positive rate `1/m` and
+    /// Rice parameter `rice_bits`.
+    ///
+    /// Duplicate items are only stored once. `m` must be non-zero and `rice_bits`
+    /// must be below 64. Setting `m` to roughly `1.497137 * 2^rice_bits` minimizes
+    /// the set's size, see [BIP158].
+    ///
+    /// Fails if the hash range, the number of distinct items times `m`, doesn't fit
+    /// into 64 bits.
+    ///
+    /// [BIP158]: https://github.com/bitcoin/bips/blob/master/bip-0158.mediawiki#user-content-Choosing_Golomb_Coding_Parameters
+    pub fn new<T: AsRef<[u8]>>(
+        items: impl IntoIterator<Item = T>,
+        rice_bits: u8,
+        m: u64,
+    ) -> Result<Self, Error> {
+        debug_assert!(m != 0);
+        debug_assert!(rice_bits < 64);
+
+        let items = items
+            .into_iter()
+            .map(|item| item.as_ref().to_vec())
+            .collect::<BTreeSet<_>>();
+        let n_elems = items.len() as u64;
+        let range = n_elems
+            .checked_mul(m)
+            .ok_or(Error::HashRangeTooLarge { n_elems, m })?;
+
+        let mut hashes = items
+            .iter()
+            .map(|item| hash_to_range(item, range))
+            .collect::<Vec<_>>();
+        hashes.sort_unstable();
+
+        let mut writer = BitWriter::default();
+        let mut previous = 0;
+        for hash in hashes {
+            writer.write_rice(hash - previous, rice_bits as u32);
+            previous = hash;
+        }
+
+        Ok(Self {
+            n_elems,
+            rice_bits,
+            m,
+            bytes: writer.finish().into_boxed_slice(),
+        })
+    }
+
+    /// Construct a set from all of its items with given false positive rate.
+    ///
+    /// `fpr` must be a number between 0 and 1 exclusive. Fails like [`new`](GolombCodedSet::new)
+    /// if the number of items times `1/fpr` doesn't fit into 64 bits.
+    pub fn new_from_fpr<T: AsRef<[u8]>>(
+        items: impl IntoIterator<Item = T>,
+        fpr: f64,
+    ) -> Result<Self, Error> {
+        debug_assert!(fpr > 0.0 && fpr < 1.0);
+
+        let m = (1.0 / fpr).ceil() as u64;
+        let rice_bits = (m as f64 / 1.497137).log2().round().clamp(0.0, 63.0) as u8;
+        Self::new(items, rice_bits, m)
+    }
+
+    /// Construct the set from existing components, e.g. when deserializing.
+    ///
+    /// Fails if `bytes` isn't a valid encoding of `n_elems` items for the given parameters.
+    pub fn new_with(n_elems: u64, rice_bits: u8, m: u64, bytes: Box<[u8]>) -> Result<Self, Error> {
+        let malformed = |reason| Error::MalformedEncoding { reason };
+        if rice_bits >= 64 {
+            return Err(malformed("invalid rice parameter"));
+        }
+        let range = n_elems
+            .checked_mul(m)
+            .ok_or_else(|| malformed("hash range too large"))?;
+
+        let mut reader = BitReader::new(&bytes);
+        let mut value = 0u64;
+        for _ in 0..n_elems {
+            let delta = reader
+                .read_rice(rice_bits as u32)
+                .ok_or_else(|| malformed("truncated set"))?;
+            value = value
+                .checked_add(delta)
+                .filter(|&value| value < range)
+                .ok_or_else(|| malformed("hash out of range"))?;
+        }
+
+        if !reader.only_padding_left() {
+            return Err(malformed("trailing data"));
+        }
+
+        Ok(Self {
+            n_elems,
+            rice_bits,
+            m,
+            bytes,
+        })
+    }
+
+    /// Check whether an item is (probably) part of the set.
+    ///
+    /// This decodes the set until it reaches the item's hash.
+    pub fn contains(&self, item: &impl AsRef<[u8]>) -> bool {
+        self.contains_any([item])
+    }
+
+    /// Check whether any of the given items is (probably) part of the set.
+    ///
+    /// This is cheaper than checking each item individually, since the set only
+    /// needs to be decoded once.
+    pub fn contains_any<T: AsRef<[u8]>>(&self, items: impl IntoIterator<Item = T>) -> bool {
+        let mut targets = items
+            .into_iter()
+            .map(|item| hash_to_range(item.as_ref(), self.n_elems * self.m))
+            .collect::<Vec<_>>();
+        targets.sort_unstable();
+
+        let mut targets = targets.into_iter().peekable();
+        for value in self.iter_hashes() {
+            while targets.next_if(|&target| target < value).is_some() {}
+            match targets.peek() {
+                None => return false,
+                Some(&target) if target == value => return true,
+                Some(_) => {}
+            }
+        }
+
+        false
+    }
+
+    /// Returns the number of distinct items in the set.
+    pub fn len(&self) -> u64 {
+        self.n_elems
+    }
+
+    /// Returns true if the set has no items.
+    pub fn is_empty(&self) -> bool {
+        self.n_elems == 0
+    }
+
+    /// Returns the Rice parameter used for encoding.
+    pub fn rice_bits(&self) -> u8 {
+        self.rice_bits
+    }
+
+    /// Returns the inverse false positive rate `M`.
+    pub fn m(&self) -> u64 {
+        self.m
+    }
+
+    /// Returns the encoded set, without its parameters.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the sorted item hashes, decoding them as it goes.
+    fn iter_hashes(&self) -> impl Iterator<Item = u64> + '_ {
+        let mut reader = BitReader::new(&self.bytes);
+        let mut value = 0u64;
+        (0..self.n_elems).map(move |_| {
+            value += reader
+                .read_rice(self.rice_bits as u32)
+                .expect("encoding is validated on construction");
+            value
+        })
+    }
+}
+
+impl Debug for GolombCodedSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GolombCodedSet")
+            .field("n_elems", &self.n_elems)
+            .field("rice_bits", &self.rice_bits)
+            .field("m", &self.m)
+            .field("bytes", &HexFieldDebug(&self.bytes))
+            .finish()
+    }
+}
+
+/// Hashes an item uniformly into `[0, range)`.
+fn hash_to_range(item: &[u8], range: u64) -> u64 {
+    let hash = xxh3::xxh3_64(item);
+    ((hash as u128 * range as u128) >> 64) as u64
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_set_contains_nothing() {
+        let set = GolombCodedSet::new(Vec::<&[u8]>::new(), 19, 784931).unwrap();
+        assert!(set.is_empty());
+        assert!(!set.contains(b"anything"));
+    }
+
+    #[test]
+    fn duplicates_are_stored_once() {
+        let set = GolombCodedSet::new([b"a", b"b", b"a"], 19, 784931).unwrap();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn round_trips_through_parts() {
+        let set = GolombCodedSet::new_from_fpr((0u32..100).map(u32::to_le_bytes), 0.001).unwrap();
+        let parts = GolombCodedSet::new_with(
+            set.len(),
+            set.rice_bits(),
+            set.m(),
+            Box::from(set.as_bytes()),
+        )
+        .unwrap();
+        assert_eq!(parts, set);
+    }
+
+    #[test]
+    fn huge_hash_ranges_are_rejected() {
+        let items = (0u32..20).map(u32::to_le_bytes);
+        assert!(matches!(
+            GolombCodedSet::new_from_fpr(items.clone(), 1e-18),
+            Err(Error::HashRangeTooLarge { n_elems: 20, .. })
+        ));
+        assert!(GolombCodedSet::new_from_fpr(items.clone().take(18), 1e-18).is_ok());
+        let set = GolombCodedSet::new(items, 63, u64::MAX / 20).unwrap();
+        assert!(set.contains(&7u32.to_le_bytes()));
+    }
+
+    #[test]
+    fn truncated_encodings_are_rejected() {
+        let set = GolombCodedSet::new_from_fpr((0u32..100).map(u32::to_le_bytes), 0.001).unwrap();
+        let truncated = &set.as_bytes()[..set.as_bytes().len() - 1];
+        let result =
+            GolombCodedSet::new_with(set.len(), set.rice_bits(), set.m(), truncated.into());
+        assert!(matches!(result, Err(Error::MalformedEncoding { .. })));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::GolombCodedSet;
+    use proptest::{collection::vec, prop_assert};
+    use test_strategy::proptest;
+
+    #[proptest]
+    fn inserted_always_contained(
+        items: Vec<u64>,
+        #[strategy(vec(proptest::num::u64::ANY, 0..10))] others: Vec<u64>,
+    ) {
+        let set =
+            GolombCodedSet::new_from_fpr(items.iter().map(|i| i.to_le_bytes()), 0.0001).unwrap();
+
+        for item in items.iter() {
+            prop_assert!(set.contains(&item.to_le_bytes()));
+
+            let mut query = others.iter().map(|i| i.to_le_bytes()).collect::<Vec<_>>();
+            query.push(item.to_le_bytes());
+            prop_assert!(set.contains_any(query));
+        }
+    }
+}
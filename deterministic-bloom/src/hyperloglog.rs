@@ -0,0 +1,326 @@
+use crate::{
+    common::Error,
+    utils::{DeserializeBytes, HexFieldDebug, SerializeBytes},
+};
+use serde::{de::Error as _, Deserialize, Serialize};
+use std::{f64::consts::LN_2, fmt::Debug};
+use xxhash_rust::xxh3;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A [HyperLogLog] sketch for estimating the number of distinct items in a set.
+///
+/// Items are hashed exactly like the first hash index of the bloom filters in this
+/// crate, i.e. with 64-bit xxh3 seeded with `0`. Like HyperLogLog++, using 64-bit
+/// hashes avoids the large range correction. Instead of HyperLogLog++'s empirical
+/// bias correction tables, cardinalities are estimated with [Ertl's improved estimator],
+/// which is unbiased over the whole range without any tables.
+///
+/// Two sketches with the same precision can be merged, which gives exactly the sketch
+/// of the union of both sets, just like OR-ing two bloom filters.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{hyperloglog::HyperLogLog, runtime_size::BloomFilter};
+///
+/// let mut filter = BloomFilter::new_from_fpr(10_000, 0.001);
+/// let mut sketch = HyperLogLog::new(12).unwrap();
+///
+/// for i in 0u32..10_000 {
+///     let item = (i % 5_000).to_le_bytes();
+///     filter.insert(&item);
+///     sketch.insert(&item);
+/// }
+///
+/// let estimate = sketch.estimate();
+/// assert!((estimate - 5_000.0).abs() < 5_000.0 * 0.05);
+/// ```
+///
+/// [HyperLogLog]: https://en.wikipedia.org/wiki/HyperLogLog
+/// [Ertl's improved estimator]: https://arxiv.org/abs/1702.01284
+#[derive(Clone, PartialEq, Eq)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Box<[u8]>,
+}
+
+/// The smallest supported precision.
+pub const MIN_PRECISION: u8 = 4;
+
+/// The largest supported precision.
+pub const MAX_PRECISION: u8 = 18;
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl HyperLogLog {
+    /// Construct an empty sketch with `2^precision` registers.
+    ///
+    /// The standard error of estimates is roughly `1.04 / sqrt(2^precision)`.
+    /// Fails if `precision` isn't between [`MIN_PRECISION`] and [`MAX_PRECISION`].
+    pub fn new(precision: u8) -> Result<Self, Error> {
+        if !(MIN_PRECISION..=MAX_PRECISION).contains(&precision) {
+            return Err(Error::InvalidPrecision { precision });
+        }
+
+        Ok(Self {
+            precision,
+            registers: vec![0u8; 1 << precision].into_boxed_slice(),
+        })
+    }
+
+    /// Add an item to the sketch.
+    pub fn insert(&mut self, item: &impl AsRef<[u8]>) {
+        self.insert_hash(xxh3::xxh3_64_with_seed(item.as_ref(), 0));
+    }
+
+    /// Add an item to the sketch by its 64-bit hash.
+    ///
+    /// This is useful when the item's hash was already computed elsewhere. The hash
+    /// needs to be computed the same way as in [`insert`](HyperLogLog::insert) for
+    /// estimates and merges to be meaningful.
+    pub fn insert_hash(&mut self, hash: u64) {
+        let index = (hash >> (64 - self.precision)) as usize;
+        let remaining = hash << self.precision;
+        let rank = (remaining.leading_zeros() as u8).min(64 - self.precision) + 1;
+
+        let register = &mut self.registers[index];
+        *register = (*register).max(rank);
+    }
+
+    /// Estimate the number of distinct items added to this sketch.
+    pub fn estimate(&self) -> f64 {
+        let q = 64 - self.precision as usize;
+        let m = self.registers.len() as f64;
+
+        let mut histogram = vec![0usize; q + 2];
+        for &register in self.registers.iter() {
+            histogram[register as usize] += 1;
+        }
+
+        let mut z = m * tau(1.0 - histogram[q + 1] as f64 / m);
+        for &count in histogram[1..=q].iter().rev() {
+            z = 0.5 * (z + count as f64);
+        }
+        z += m * sigma(histogram[0] as f64 / m);
+
+        m * m / (2.0 * LN_2 * z)
+    }
+
+    /// Merge another sketch into this one, after which this sketch represents the
+    /// union of both sets.
+    ///
+    /// Fails if the sketches have different precisions.
+    pub fn merge(&mut self, other: &HyperLogLog) -> Result<(), Error> {
+        if self.precision != other.precision {
+            return Err(Error::PrecisionMismatch {
+                expected: self.precision,
+                actual: other.precision,
+            });
+        }
+
+        for (register, other_register) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = (*register).max(*other_register);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the precision, i.e. the binary logarithm of the number of registers.
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// Returns the canonical byte representation: the precision followed by one
+    /// byte per register.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.registers.len() + 1);
+        bytes.push(self.precision);
+        bytes.extend_from_slice(&self.registers);
+        bytes
+    }
+
+    /// Construct a sketch from its canonical byte representation.
+    ///
+    /// Fails if the precision is invalid, the number of registers doesn't match it,
+    /// or any register holds an impossible value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let (&precision, registers) = bytes.split_first().ok_or(Error::MalformedEncoding {
+            reason: "missing precision",
+        })?;
+
+        let mut sketch = Self::new(precision)?;
+        if registers.len() != sketch.registers.len() {
+            return Err(Error::VectorImportSizeMismatch {
+                expected: sketch.registers.len(),
+                actual: registers.len(),
+            });
+        }
+
+        if registers.iter().any(|&register| register > 65 - precision) {
+            return Err(Error::MalformedEncoding {
+                reason: "register value out of range",
+            });
+        }
+
+        sketch.registers.copy_from_slice(registers);
+        Ok(sketch)
+    }
+}
+
+impl Serialize for HyperLogLog {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        SerializeBytes(&self.to_bytes()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for HyperLogLog {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let DeserializeBytes(bytes) = DeserializeBytes::deserialize(deserializer)?;
+        HyperLogLog::from_bytes(&bytes).map_err(D::Error::custom)
+    }
+}
+
+impl Debug for HyperLogLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HyperLogLog")
+            .field("precision", &self.precision)
+            .field("registers", &HexFieldDebug(&self.registers))
+            .finish()
+    }
+}
+
+/// The `σ` function from Ertl's improved estimator, correcting for empty registers.
+fn sigma(mut x: f64) -> f64 {
+    if x == 1.0 {
+        return f64::INFINITY;
+    }
+
+    let mut y = 1.0;
+    let mut z = x;
+    loop {
+        x *= x;
+        let previous = z;
+        z += x * y;
+        y += y;
+        if z == previous {
+            return z;
+        }
+    }
+}
+
+/// The `τ` function from Ertl's improved estimator, correcting for saturated registers.
+fn tau(mut x: f64) -> f64 {
+    if x == 0.0 || x == 1.0 {
+        return 0.0;
+    }
+
+    let mut y = 1.0;
+    let mut z = 1.0 - x;
+    loop {
+        x = x.sqrt();
+        let previous = z;
+        y *= 0.5;
+        z -= (1.0 - x).powi(2) * y;
+        if z == previous {
+            return z / 3.0;
+        }
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_sketch_estimates_zero() {
+        let sketch = HyperLogLog::new(10).unwrap();
+        assert_eq!(sketch.estimate(), 0.0);
+    }
+
+    #[test]
+    fn invalid_precisions_are_rejected() {
+        assert!(HyperLogLog::new(3).is_err());
+        assert!(HyperLogLog::new(19).is_err());
+    }
+
+    #[test]
+    fn serialized_sketch_can_be_deserialized_correctly() {
+        let mut sketch = HyperLogLog::new(8).unwrap();
+        for i in 0u32..100 {
+            sketch.insert(&i.to_le_bytes());
+        }
+
+        let ipld = libipld::serde::to_ipld(&sketch).unwrap();
+        let deserialized: HyperLogLog = libipld::serde::from_ipld(ipld).unwrap();
+
+        assert_eq!(deserialized, sketch);
+    }
+
+    #[test]
+    fn merging_different_precisions_fails() {
+        let mut a = HyperLogLog::new(8).unwrap();
+        let b = HyperLogLog::new(9).unwrap();
+        assert!(matches!(a.merge(&b), Err(Error::PrecisionMismatch { .. })));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::HyperLogLog;
+    use proptest::{collection::vec, prop_assert, prop_assert_eq};
+    use test_strategy::proptest;
+
+    #[proptest(cases = 100)]
+    fn merge_equals_sketch_of_union(
+        #[strategy(vec(proptest::num::u32::ANY, 0..500))] a: Vec<u32>,
+        #[strategy(vec(proptest::num::u32::ANY, 0..500))] b: Vec<u32>,
+    ) {
+        let mut sketch_a = HyperLogLog::new(8).unwrap();
+        let mut sketch_b = HyperLogLog::new(8).unwrap();
+        let mut sketch_union = HyperLogLog::new(8).unwrap();
+
+        for item in a.iter() {
+            sketch_a.insert(&item.to_le_bytes());
+            sketch_union.insert(&item.to_le_bytes());
+        }
+        for item in b.iter() {
+            sketch_b.insert(&item.to_le_bytes());
+            sketch_union.insert(&item.to_le_bytes());
+        }
+
+        sketch_a.merge(&sketch_b).unwrap();
+        prop_assert_eq!(&sketch_a, &sketch_union);
+        prop_assert_eq!(
+            HyperLogLog::from_bytes(&sketch_a.to_bytes()).unwrap(),
+            sketch_a
+        );
+    }
+
+    #[proptest(cases = 20)]
+    fn estimates_are_close(#[strategy(1u32..50_000)] n: u32) {
+        let mut sketch = HyperLogLog::new(14).unwrap();
+        for i in 0..n {
+            sketch.insert(&i.to_le_bytes());
+        }
+
+        // 5 standard errors
+        let tolerance = 5.0 * 1.04 / 128.0;
+        let estimate = sketch.estimate();
+        prop_assert!((estimate - n as f64).abs() <= n as f64 * tolerance + 1.0);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::common::Error;
+use libipld::{
+    cbor::DagCborCodec,
+    codec::Codec,
+    multihash::{Code, MultihashDigest},
+    Cid, Ipld,
+};
+
+//------------------------------------------------------------------------------
+// Constants
+//------------------------------------------------------------------------------
+
+/// The [IPLD schema] of the canonical IPLD representation of bloom filters.
+///
+/// Both [`runtime_size::BloomFilter`](crate::runtime_size::BloomFilter) and
+/// [`const_size::BloomFilter`](crate::const_size::BloomFilter) use this representation,
+/// so filters with the same parameters and bits have the same [CID](crate::runtime_size::BloomFilter::cid),
+/// no matter which type produced them. It matches the serde representation of
+/// `runtime_size::BloomFilter`.
+///
+/// [IPLD schema]: https://ipld.io/docs/schemas/
+pub const SCHEMA: &str = "\
+type BloomFilter struct {
+  kHashes Int
+  bytes Bytes
+} representation tuple
+";
+
+//------------------------------------------------------------------------------
+// Functions
+//------------------------------------------------------------------------------
+
+/// Build the canonical IPLD representation of a filter.
+pub(crate) fn to_ipld(k_hashes: usize, bytes: &[u8]) -> Ipld {
+    Ipld::List(vec![
+        Ipld::Integer(k_hashes as i128),
+        Ipld::Bytes(bytes.to_vec()),
+    ])
+}
+
+/// Parse the canonical IPLD representation of a filter into `k_hashes` and its bytes.
+pub(crate) fn from_ipld(ipld: &Ipld) -> Result<(usize, &[u8]), Error> {
+    let malformed = |reason| Error::MalformedEncoding { reason };
+    match ipld {
+        Ipld::List(fields) => match fields.as_slice() {
+            [Ipld::Integer(k_hashes), Ipld::Bytes(bytes)] => {
+                let k_hashes = usize::try_from(*k_hashes)
+                    .ok()
+                    .filter(|&k_hashes| k_hashes != 0)
+                    .ok_or_else(|| malformed("k_hashes must be a non-zero integer"))?;
+                Ok((k_hashes, bytes))
+            }
+            _ => Err(malformed("expected a tuple of k_hashes and bytes")),
+        },
+        _ => Err(malformed("expected a list")),
+    }
+}
+
+/// Compute the CIDv1 of the canonical DAG-CBOR encoding of a filter.
+pub(crate) fn cid(k_hashes: usize, bytes: &[u8], code: Code) -> Cid {
+    let encoded = DagCborCodec
+        .encode(&to_ipld(k_hashes, bytes))
+        .expect("encoding lists of integers and bytes can't fail");
+    Cid::new_v1(DagCborCodec.into(), code.digest(&encoded))
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{const_size, runtime_size};
+
+    #[test]
+    fn both_filter_types_have_the_same_cid() {
+        let mut runtime = runtime_size::BloomFilter::new_with(30, Box::new([0u8; 256]));
+        let mut constant = const_size::BloomFilter::<256, 30>::new();
+        runtime.insert(b"Hello");
+        constant.insert(b"Hello");
+
+        assert_eq!(runtime.cid(Code::Sha2_256), constant.cid(Code::Sha2_256));
+        assert_ne!(runtime.cid(Code::Sha2_256), runtime.cid(Code::Sha2_512));
+    }
+
+    #[test]
+    fn round_trips_through_dag_cbor_blocks() {
+        let mut filter = runtime_size::BloomFilter::new_from_fpr(100, 0.001);
+        filter.insert(b"Hello");
+
+        let encoded = DagCborCodec.encode(&filter).unwrap();
+        let decoded: runtime_size::BloomFilter = DagCborCodec.decode(&encoded).unwrap();
+        assert_eq!(decoded, filter);
+
+        let constant: Result<const_size::BloomFilter<256, 30>, _> = DagCborCodec.decode(&encoded);
+        assert!(constant.is_err());
+    }
+
+    #[test]
+    fn serde_and_dag_cbor_representations_match() {
+        let mut runtime = runtime_size::BloomFilter::new_from_fpr(100, 0.001);
+        runtime.insert(b"Hello");
+
+        assert_eq!(
+            libipld::serde::to_ipld(&runtime).unwrap(),
+            runtime.to_ipld()
+        );
+    }
+
+    #[test]
+    fn invalid_representations_are_rejected() {
+        let ipld = Ipld::List(vec![Ipld::Integer(0), Ipld::Bytes(vec![0; 8])]);
+        assert!(from_ipld(&ipld).is_err());
+        assert!(from_ipld(&Ipld::Bytes(vec![0; 8])).is_err());
+    }
+}
@@ -6,13 +6,76 @@
 //!
 //! This Crate is intented as a solid basis for cache reproducability
 //! and for underlying certain cryptographic primitives.
+//!
+//! # Feature flags
+//!
+//! - `blake3`: Enables the `merkle` and `secure_index` modules, which hash with BLAKE3.
+//! - `constant_time`: Adds constant-time membership checks and comparisons to both
+//!   [`runtime_size::BloomFilter`] and [`const_size::BloomFilter`], and makes `==` on
+//!   them compare in constant time as well. Their ordering (`<`, `cmp`, ...) stays
+//!   variable-time. Both types also zeroize their bits when dropped. As they implement
+//!   [`Drop`] then, they can't be destructured or moved out of anymore, so code that
+//!   compiles without this feature may not compile with it.
+//! - `ipld`: Enables the `ipld` module, CIDs and DAG-CBOR codecs for filters. CIDs can be
+//!   hashed with the SHA-2 codes of `libipld::multihash::Code`.
+//! - `mmap`: Enables the `mmap` module for filters backed by memory-mapped files.
+//! - `multibase`: Enables encoding filters as multibase strings and parsing them with
+//!   [`FromStr`](std::str::FromStr).
 
+/// Bloom filters that store their bits sparsely while mostly empty
+pub mod adaptive;
+/// Attenuated bloom filters for probabilistic routing across multiple hops
+pub mod attenuated;
+/// Bit-sliced signature indices for querying many same-size bloom filters at once
+pub mod bit_sliced;
+/// Static approximate key-value maps (Bloomier filters) storing a few bits per key
+pub mod bloomier;
 /// Some structs and implementations that multiple bloom implementations can depend on
 pub mod common;
+/// Compressed transport encoding for bloom filter bit arrays
+pub mod compressed;
 /// Bloom filters with compile-time-determinted parameters (size & hash count)
 pub mod const_size;
+/// Constants of the self-describing, versioned container format for archiving filters
+pub mod container;
+/// Grow-only CRDTs for replicating filters
+pub mod crdt;
+/// Delta encoding of changes between two versions of a filter
+pub mod delta;
+/// Garbled bloom filters for private set intersection
+pub mod garbled;
+/// Golomb-coded sets, static and more compact alternatives to bloom filters
+pub mod gcs;
+/// HyperLogLog sketches for estimating the number of distinct items
+pub mod hyperloglog;
+/// The canonical IPLD representation of bloom filters and their CIDs
+#[cfg(feature = "ipld")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+pub mod ipld;
+/// Chunked filters hashed into Merkle trees, for proving single membership answers
+#[cfg(feature = "blake3")]
+#[cfg_attr(docsrs, doc(cfg(feature = "blake3")))]
+pub mod merkle;
+/// MinHash signatures for estimating the similarity of sets
+pub mod minhash;
+/// Bloom filters backed by memory-mapped files
+#[cfg(feature = "mmap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "mmap")))]
+pub mod mmap;
+/// Bloom filters over hierarchical paths that support querying for descendants
+pub mod path;
+/// Differentially private release of bloom filters via randomized response
+pub mod privacy;
+/// Range filters answering whether any key in an interval was inserted
+pub mod range;
 /// Bloom filters with runtime-determined parameters. Their size can be chosen
 /// arbitrarily at runtime, but not be modified during use (they're not resizable).
 pub mod runtime_size;
+/// Goh-style secure indexes for keyword search over encrypted documents
+#[cfg(feature = "blake3")]
+#[cfg_attr(docsrs, doc(cfg(feature = "blake3")))]
+pub mod secure_index;
+/// Nearest-neighbour search over collections of bloom filters
+pub mod similarity;
 
 mod utils;
@@ -0,0 +1,588 @@
+use crate::{
+    common::{BloomParams, Error, HashIndexIterator},
+    runtime_size::BloomFilter,
+    utils::{DeserializeBytes, HexFieldDebug, SerializeBytes},
+};
+use bitvec::{prelude::Lsb0, view::BitView};
+use serde::{de::Error as _, Deserialize, Serialize};
+use std::{collections::BTreeSet, fmt::Debug};
+
+//------------------------------------------------------------------------------
+// Constants
+//------------------------------------------------------------------------------
+
+/// Domain separation tag of leaf hashes.
+const LEAF_TAG: u8 = 0;
+
+/// Domain separation tag of inner node hashes.
+const NODE_TAG: u8 = 1;
+
+/// Domain separation tag of the root, which commits to the filter's parameters.
+const ROOT_TAG: u8 = 2;
+
+/// The hash of leaves padding the tree to a power of two leaves.
+const PADDING: [u8; 32] = [0; 32];
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A bloom filter whose bits are split into fixed-size chunks and hashed into a
+/// [Merkle tree], so single `contains` answers can be proven without the whole filter.
+///
+/// Whoever publishes the filter publishes its [`root`](MerkleBloomFilter::root). A light
+/// client asks a server for a [proof](MerkleBloomFilter::prove_contains) of an item,
+/// which holds only the (at most `k`) chunks containing the item's bits and their Merkle
+/// paths, and checks it with [`verify_contains`]. This proves both positive and negative
+/// answers, since the proof contains all bits the answer depends on.
+///
+/// The tree uses BLAKE3 with domain separated leaves and nodes, and is padded to a power
+/// of two leaves. The root additionally commits to `k_hashes`, the byte size and the
+/// chunk size, so a proof can't claim different parameters than the published filter.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{merkle::{verify_contains, MerkleBloomFilter}, runtime_size::BloomFilter};
+///
+/// let mut filter = BloomFilter::new_from_fpr_po2(10_000, 0.001);
+/// filter.insert(b"Hello");
+///
+/// let merkle = MerkleBloomFilter::new(filter, 256).unwrap();
+/// let root = merkle.root();
+///
+/// let proof = merkle.prove_contains(b"Hello");
+/// assert!(verify_contains(&root, b"Hello", &proof).unwrap());
+///
+/// let proof = merkle.prove_contains(b"World!");
+/// assert!(!verify_contains(&root, b"World!", &proof).unwrap());
+/// ```
+///
+/// [Merkle tree]: https://en.wikipedia.org/wiki/Merkle_tree
+#[derive(Clone, PartialEq, Eq)]
+pub struct MerkleBloomFilter {
+    filter: BloomFilter,
+    chunk_size: usize,
+    /// The levels of the tree, from the padded leaves up to the tree root.
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+/// A proof of the chunks of a [`MerkleBloomFilter`] that an item's bits fall into.
+///
+/// Created with [`MerkleBloomFilter::prove_contains`] and checked with [`verify_contains`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct ContainsProof {
+    k_hashes: usize,
+    byte_size: usize,
+    chunk_size: usize,
+    chunks: Vec<ChunkProof>,
+}
+
+/// A single chunk and the sibling hashes on its path to the tree root.
+#[derive(Clone, PartialEq, Eq)]
+struct ChunkProof {
+    index: usize,
+    bytes: Vec<u8>,
+    path: Vec<[u8; 32]>,
+}
+
+//------------------------------------------------------------------------------
+// Functions
+//------------------------------------------------------------------------------
+
+/// Check a [`ContainsProof`] for `item` against the `root` of a [`MerkleBloomFilter`],
+/// returning whether the filter (probably) contains the item.
+///
+/// Fails with [`Error::InvalidProof`] if the proof doesn't match the root or doesn't
+/// hold exactly the chunks the item's bits fall into.
+pub fn verify_contains(
+    root: &[u8; 32],
+    item: &impl AsRef<[u8]>,
+    proof: &ContainsProof,
+) -> Result<bool, Error> {
+    let invalid = |reason| Error::InvalidProof { reason };
+    if proof.k_hashes == 0
+        || proof.chunk_size == 0
+        || proof.byte_size == 0
+        || proof.byte_size % proof.chunk_size != 0
+    {
+        return Err(invalid("invalid filter parameters"));
+    }
+    let bit_size = proof
+        .byte_size
+        .checked_mul(8)
+        .ok_or_else(|| invalid("invalid filter parameters"))?;
+    let chunk_bits = proof.chunk_size * 8;
+    let depth = depth(proof.byte_size / proof.chunk_size);
+    if proof.chunks.len() > proof.k_hashes {
+        return Err(invalid("proof holds too many chunks"));
+    }
+
+    // Check the proof against the root first: It commits to `k_hashes`, so we only
+    // compute as many hash indices as the filter's owner chose, not as many as the
+    // proof claims.
+    let mut tree_root = None;
+    for chunk in proof.chunks.iter() {
+        if chunk.bytes.len() != proof.chunk_size || chunk.path.len() != depth {
+            return Err(invalid("malformed chunk"));
+        }
+        let computed = chunk.path.iter().enumerate().fold(
+            leaf_hash(&chunk.bytes),
+            |node, (level, sibling)| {
+                if (chunk.index >> level) & 1 == 0 {
+                    node_hash(&node, sibling)
+                } else {
+                    node_hash(sibling, &node)
+                }
+            },
+        );
+        if *tree_root.get_or_insert(computed) != computed {
+            return Err(invalid("chunks lead to different roots"));
+        }
+    }
+
+    let tree_root = tree_root.ok_or_else(|| invalid("proof holds no chunks"))?;
+    if root_hash(
+        proof.k_hashes,
+        proof.byte_size,
+        proof.chunk_size,
+        &tree_root,
+    ) != *root
+    {
+        return Err(invalid("root doesn't match"));
+    }
+
+    let indices = HashIndexIterator::new(item, bit_size)
+        .take(proof.k_hashes)
+        .collect::<Vec<_>>();
+    let chunks = indices
+        .iter()
+        .map(|index| index / chunk_bits)
+        .collect::<BTreeSet<_>>();
+    if !chunks
+        .iter()
+        .copied()
+        .eq(proof.chunks.iter().map(|chunk| chunk.index))
+    {
+        return Err(invalid("proof doesn't hold the item's chunks"));
+    }
+
+    Ok(indices.iter().all(|index| {
+        let chunk = &proof.chunks[chunks.range(..index / chunk_bits).count()];
+        chunk.bytes.view_bits::<Lsb0>()[index % chunk_bits]
+    }))
+}
+
+/// Returns the depth of a tree with `leaf_count` leaves padded to a power of two.
+fn depth(leaf_count: usize) -> usize {
+    leaf_count.next_power_of_two().trailing_zeros() as usize
+}
+
+fn leaf_hash(chunk: &[u8]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[LEAF_TAG]);
+    hasher.update(chunk);
+    *hasher.finalize().as_bytes()
+}
+
+fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[NODE_TAG]);
+    hasher.update(left);
+    hasher.update(right);
+    *hasher.finalize().as_bytes()
+}
+
+fn root_hash(
+    k_hashes: usize,
+    byte_size: usize,
+    chunk_size: usize,
+    tree_root: &[u8; 32],
+) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[ROOT_TAG]);
+    hasher.update(&(k_hashes as u64).to_le_bytes());
+    hasher.update(&(byte_size as u64).to_le_bytes());
+    hasher.update(&(chunk_size as u64).to_le_bytes());
+    hasher.update(tree_root);
+    *hasher.finalize().as_bytes()
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl MerkleBloomFilter {
+    /// Split `filter` into chunks of `chunk_size` bytes and hash them into a Merkle tree.
+    ///
+    /// Fails if the chunk size doesn't evenly divide the filter's bytes.
+    pub fn new(filter: BloomFilter, chunk_size: usize) -> Result<Self, Error> {
+        let byte_size = filter.as_bytes().len();
+        if chunk_size == 0 || byte_size == 0 || byte_size % chunk_size != 0 {
+            return Err(Error::InvalidChunkSize {
+                chunk_size,
+                byte_size,
+            });
+        }
+
+        let leaf_count = byte_size / chunk_size;
+        let mut leaves = filter
+            .as_bytes()
+            .chunks(chunk_size)
+            .map(leaf_hash)
+            .collect::<Vec<_>>();
+        leaves.resize(leaf_count.next_power_of_two(), PADDING);
+
+        let mut levels = vec![leaves];
+        while levels[levels.len() - 1].len() > 1 {
+            let parents = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| node_hash(&pair[0], &pair[1]))
+                .collect();
+            levels.push(parents);
+        }
+
+        Ok(Self {
+            filter,
+            chunk_size,
+            levels,
+        })
+    }
+
+    /// Insert an element into the filter, rehashing the paths of the chunks it falls into.
+    pub fn insert(&mut self, item: &impl AsRef<[u8]>) {
+        self.filter.insert(item);
+
+        let chunk_bits = self.chunk_size * 8;
+        let chunks = self
+            .filter
+            .hash_indices(item)
+            .map(|index| index / chunk_bits)
+            .collect::<BTreeSet<_>>();
+        for chunk in chunks {
+            let start = chunk * self.chunk_size;
+            self.levels[0][chunk] =
+                leaf_hash(&self.filter.as_bytes()[start..start + self.chunk_size]);
+            let mut index = chunk;
+            for level in 1..self.levels.len() {
+                index /= 2;
+                let children = &self.levels[level - 1];
+                self.levels[level][index] =
+                    node_hash(&children[2 * index], &children[2 * index + 1]);
+            }
+        }
+    }
+
+    /// Check whether an element was (probably) added into the filter.
+    pub fn contains(&self, item: &impl AsRef<[u8]>) -> bool {
+        self.filter.contains(item)
+    }
+
+    /// Returns the root to publish, which commits to the filter's bits and parameters.
+    pub fn root(&self) -> [u8; 32] {
+        root_hash(
+            self.filter.hash_count(),
+            self.filter.as_bytes().len(),
+            self.chunk_size,
+            &self.levels[self.levels.len() - 1][0],
+        )
+    }
+
+    /// Prove whether the filter contains an item, see [`verify_contains`].
+    pub fn prove_contains(&self, item: &impl AsRef<[u8]>) -> ContainsProof {
+        let chunk_bits = self.chunk_size * 8;
+        let chunks = self
+            .filter
+            .hash_indices(item)
+            .map(|index| index / chunk_bits)
+            .collect::<BTreeSet<_>>();
+
+        let chunks = chunks
+            .into_iter()
+            .map(|index| {
+                let start = index * self.chunk_size;
+                let path = self.levels[..self.levels.len() - 1]
+                    .iter()
+                    .enumerate()
+                    .map(|(level, nodes)| nodes[(index >> level) ^ 1])
+                    .collect();
+                ChunkProof {
+                    index,
+                    bytes: self.filter.as_bytes()[start..start + self.chunk_size].to_vec(),
+                    path,
+                }
+            })
+            .collect();
+
+        ContainsProof {
+            k_hashes: self.filter.hash_count(),
+            byte_size: self.filter.as_bytes().len(),
+            chunk_size: self.chunk_size,
+            chunks,
+        }
+    }
+
+    /// Returns the size of chunks in bytes.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Returns the underlying filter.
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+
+    /// Returns the underlying filter, dropping the tree.
+    pub fn into_filter(self) -> BloomFilter {
+        self.filter
+    }
+}
+
+impl ContainsProof {
+    /// Returns the parameters of the filter this proof claims to be about.
+    pub fn bloom_params(&self) -> BloomParams {
+        BloomParams {
+            byte_size: self.byte_size,
+            k_hashes: self.k_hashes,
+        }
+    }
+
+    /// Returns the size of chunks in bytes.
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+    }
+
+    /// Returns the number of chunks in this proof.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+}
+
+/// Serializes as a tuple of `k_hashes`, the byte size, the chunk size and a list of
+/// tuples of each chunk's index, its bytes and the sibling hashes on its path.
+impl Serialize for ContainsProof {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let chunks = self
+            .chunks
+            .iter()
+            .map(|chunk| {
+                (
+                    chunk.index as u64,
+                    SerializeBytes(&chunk.bytes),
+                    chunk
+                        .path
+                        .iter()
+                        .map(|hash| SerializeBytes(hash))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        (
+            self.k_hashes as u64,
+            self.byte_size as u64,
+            self.chunk_size as u64,
+            chunks,
+        )
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ContainsProof {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        type Chunk = (u64, DeserializeBytes, Vec<DeserializeBytes>);
+        let (k_hashes, byte_size, chunk_size, chunks) =
+            <(u64, u64, u64, Vec<Chunk>)>::deserialize(deserializer)?;
+        let to_usize = |value: u64| {
+            usize::try_from(value).map_err(|_| {
+                D::Error::custom(Error::MalformedEncoding {
+                    reason: "value too large",
+                })
+            })
+        };
+
+        let chunks = chunks
+            .into_iter()
+            .map(|(index, DeserializeBytes(bytes), path)| {
+                let path = path
+                    .into_iter()
+                    .map(|DeserializeBytes(hash)| {
+                        hash.try_into().map_err(|_| {
+                            D::Error::custom(Error::MalformedEncoding {
+                                reason: "hashes must be 32 bytes",
+                            })
+                        })
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(ChunkProof {
+                    index: to_usize(index)?,
+                    bytes,
+                    path,
+                })
+            })
+            .collect::<Result<_, D::Error>>()?;
+
+        Ok(Self {
+            k_hashes: to_usize(k_hashes)?,
+            byte_size: to_usize(byte_size)?,
+            chunk_size: to_usize(chunk_size)?,
+            chunks,
+        })
+    }
+}
+
+impl Debug for MerkleBloomFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MerkleBloomFilter")
+            .field("filter", &self.filter)
+            .field("chunk_size", &self.chunk_size)
+            .field("root", &HexFieldDebug(self.root()))
+            .finish()
+    }
+}
+
+impl Debug for ContainsProof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ContainsProof")
+            .field("k_hashes", &self.k_hashes)
+            .field("byte_size", &self.byte_size)
+            .field("chunk_size", &self.chunk_size)
+            .field("chunks", &self.chunks)
+            .finish()
+    }
+}
+
+impl Debug for ChunkProof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkProof")
+            .field("index", &self.index)
+            .field("bytes", &HexFieldDebug(&self.bytes))
+            .field("path_len", &self.path.len())
+            .finish()
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn merkle_filter() -> MerkleBloomFilter {
+        let mut filter = BloomFilter::new_from_fpr_po2(1_000, 0.001);
+        for i in 0u32..500 {
+            filter.insert(&i.to_le_bytes());
+        }
+        let chunk_size = filter.as_bytes().len() / 16;
+        MerkleBloomFilter::new(filter, chunk_size).unwrap()
+    }
+
+    #[test]
+    fn inserts_update_the_root() {
+        let mut merkle = merkle_filter();
+        let root = merkle.root();
+        merkle.insert(b"Hello");
+
+        let rebuilt = MerkleBloomFilter::new(merkle.filter().clone(), merkle.chunk_size()).unwrap();
+        assert_ne!(merkle.root(), root);
+        assert_eq!(merkle.root(), rebuilt.root());
+
+        let proof = merkle.prove_contains(b"Hello");
+        assert!(verify_contains(&merkle.root(), b"Hello", &proof).unwrap());
+        assert!(verify_contains(&root, b"Hello", &proof).is_err());
+    }
+
+    #[test]
+    fn tampered_proofs_are_rejected() {
+        let merkle = merkle_filter();
+        let root = merkle.root();
+        let item = 1_000u32.to_le_bytes();
+        let proof = merkle.prove_contains(&item);
+        assert!(!verify_contains(&root, &item, &proof).unwrap());
+
+        let mut flipped = proof.clone();
+        for byte in flipped.chunks[0].bytes.iter_mut() {
+            *byte = 0xFF;
+        }
+        assert!(verify_contains(&root, &item, &flipped).is_err());
+
+        let mut missing = proof.clone();
+        missing.chunks.pop();
+        assert!(verify_contains(&root, &item, &missing).is_err());
+
+        let mut params = proof.clone();
+        params.k_hashes += 1;
+        assert!(verify_contains(&root, &item, &params).is_err());
+        params.k_hashes = usize::MAX;
+        assert!(verify_contains(&root, &item, &params).is_err());
+
+        assert!(verify_contains(&root, b"another item", &proof).is_err());
+    }
+
+    #[test]
+    fn chunk_sizes_are_validated() {
+        let filter = BloomFilter::new_with(3, Box::new([0; 100]));
+        assert!(matches!(
+            MerkleBloomFilter::new(filter.clone(), 0),
+            Err(Error::InvalidChunkSize { .. })
+        ));
+        assert!(matches!(
+            MerkleBloomFilter::new(filter.clone(), 30),
+            Err(Error::InvalidChunkSize { .. })
+        ));
+        assert!(MerkleBloomFilter::new(filter, 100).is_ok());
+    }
+
+    #[test]
+    fn serialized_proof_can_be_deserialized_correctly() {
+        let merkle = merkle_filter();
+        let proof = merkle.prove_contains(&7u32.to_le_bytes());
+
+        let ipld = libipld::serde::to_ipld(&proof).unwrap();
+        let deserialized: ContainsProof = libipld::serde::from_ipld(ipld).unwrap();
+
+        assert_eq!(deserialized, proof);
+        assert!(verify_contains(&merkle.root(), &7u32.to_le_bytes(), &deserialized).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::{verify_contains, MerkleBloomFilter};
+    use crate::runtime_size::BloomFilter;
+    use proptest::{collection::vec, prop_assert_eq};
+    use test_strategy::proptest;
+
+    #[proptest]
+    fn verified_answers_match_contains(
+        #[strategy(vec(proptest::num::u32::ANY, 0..100))] inserted: Vec<u32>,
+        #[strategy(vec(proptest::num::u32::ANY, 1..20))] queried: Vec<u32>,
+        #[strategy(0usize..3)] chunk_shift: usize,
+    ) {
+        let mut merkle = MerkleBloomFilter::new(
+            BloomFilter::new_with(4, Box::new([0; 48])),
+            4 << chunk_shift,
+        )
+        .unwrap();
+        for item in inserted.iter() {
+            merkle.insert(&item.to_le_bytes());
+        }
+
+        let root = merkle.root();
+        for item in inserted.iter().chain(queried.iter()) {
+            let item = item.to_le_bytes();
+            let proof = merkle.prove_contains(&item);
+            prop_assert_eq!(
+                verify_contains(&root, &item, &proof).unwrap(),
+                merkle.contains(&item)
+            );
+        }
+    }
+}
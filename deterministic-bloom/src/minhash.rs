@@ -0,0 +1,256 @@
+use crate::{
+    common::Error,
+    utils::{DeserializeBytes, SerializeBytes},
+};
+use serde::{de::Error as _, Deserialize, Serialize};
+use std::fmt::Debug;
+use xxhash_rust::xxh3;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A [MinHash] signature for estimating the Jaccard similarity of two sets.
+///
+/// The signature consists of `k` slots, where slot `i` holds the minimum of
+/// `xxh3(item, seed = i)` over all items in the set. These are exactly the hash
+/// evaluations the bloom filters in this crate use for their hash indices, so
+/// signatures are deterministic and comparable across peers.
+///
+/// The fraction of slots two signatures agree on is an unbiased estimate of the
+/// sets' Jaccard similarity, with a standard error of at most `1 / (2 * sqrt(k))`.
+/// Unlike estimates derived from bloom filter popcounts, this stays accurate no
+/// matter how many items the sets contain.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::minhash::MinHash;
+///
+/// let mut a = MinHash::new(256);
+/// let mut b = MinHash::new(256);
+///
+/// for i in 0u32..1000 {
+///     a.insert(&i.to_le_bytes());
+/// }
+/// for i in 500u32..1500 {
+///     b.insert(&i.to_le_bytes());
+/// }
+///
+/// // The true Jaccard similarity is 500 / 1500
+/// let similarity = a.jaccard(&b).unwrap();
+/// assert!((similarity - 1.0 / 3.0).abs() < 0.1);
+/// ```
+///
+/// [MinHash]: https://en.wikipedia.org/wiki/MinHash
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct MinHash {
+    slots: Box<[u64]>,
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl MinHash {
+    /// Construct the signature of an empty set with `num_hashes` slots.
+    pub fn new(num_hashes: usize) -> Self {
+        Self {
+            slots: vec![u64::MAX; num_hashes].into_boxed_slice(),
+        }
+    }
+
+    /// Construct a signature from existing slots, e.g. when deserializing.
+    pub fn new_with(slots: Box<[u64]>) -> Self {
+        Self { slots }
+    }
+
+    /// Add an item to the set.
+    pub fn insert(&mut self, item: &impl AsRef<[u8]>) {
+        for (seed, slot) in self.slots.iter_mut().enumerate() {
+            let hash = xxh3::xxh3_64_with_seed(item.as_ref(), seed as u64);
+            *slot = (*slot).min(hash);
+        }
+    }
+
+    /// Estimate the Jaccard similarity of both sets, as number between 0 and 1.
+    ///
+    /// Two empty sets are considered identical. Fails if the signatures have
+    /// different numbers of slots.
+    pub fn jaccard(&self, other: &MinHash) -> Result<f64, Error> {
+        self.check_compatible(other)?;
+
+        if self.slots.is_empty() {
+            return Ok(1.0);
+        }
+
+        let matching = self
+            .slots
+            .iter()
+            .zip(other.slots.iter())
+            .filter(|(a, b)| a == b)
+            .count();
+
+        Ok(matching as f64 / self.slots.len() as f64)
+    }
+
+    /// Merge another signature into this one, after which this is the signature
+    /// of the union of both sets.
+    ///
+    /// Fails if the signatures have different numbers of slots.
+    pub fn merge(&mut self, other: &MinHash) -> Result<(), Error> {
+        self.check_compatible(other)?;
+
+        for (slot, other_slot) in self.slots.iter_mut().zip(other.slots.iter()) {
+            *slot = (*slot).min(*other_slot);
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if no items were added to the set.
+    pub fn is_empty(&self) -> bool {
+        self.slots.iter().all(|&slot| slot == u64::MAX)
+    }
+
+    /// Returns the number of slots, i.e. hash functions used.
+    pub fn num_hashes(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the slots of this signature.
+    pub fn slots(&self) -> &[u64] {
+        &self.slots
+    }
+
+    /// Returns the canonical byte representation: each slot as little-endian `u64`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.slots
+            .iter()
+            .flat_map(|slot| slot.to_le_bytes())
+            .collect()
+    }
+
+    /// Construct a signature from its canonical byte representation.
+    ///
+    /// Fails if the number of bytes isn't a multiple of 8.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() % 8 != 0 {
+            return Err(Error::MalformedEncoding {
+                reason: "length is not a multiple of 8",
+            });
+        }
+
+        let slots = bytes
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().expect("chunks are 8 bytes")))
+            .collect();
+
+        Ok(Self { slots })
+    }
+
+    fn check_compatible(&self, other: &MinHash) -> Result<(), Error> {
+        if self.slots.len() != other.slots.len() {
+            return Err(Error::SignatureSizeMismatch {
+                expected: self.slots.len(),
+                actual: other.slots.len(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for MinHash {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        SerializeBytes(&self.to_bytes()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MinHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let DeserializeBytes(bytes) = DeserializeBytes::deserialize(deserializer)?;
+        MinHash::from_bytes(&bytes).map_err(D::Error::custom)
+    }
+}
+
+impl Debug for MinHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("MinHash").field(&self.slots).finish()
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_signatures_are_identical() {
+        let a = MinHash::new(16);
+        assert!(a.is_empty());
+        assert_eq!(a.jaccard(&MinHash::new(16)).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn mismatching_sizes_are_rejected() {
+        let a = MinHash::new(16);
+        assert!(matches!(
+            a.jaccard(&MinHash::new(32)),
+            Err(Error::SignatureSizeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn serialized_signature_can_be_deserialized_correctly() {
+        let mut signature = MinHash::new(64);
+        signature.insert(b"Hello");
+
+        let ipld = libipld::serde::to_ipld(&signature).unwrap();
+        let deserialized: MinHash = libipld::serde::from_ipld(ipld).unwrap();
+
+        assert_eq!(deserialized, signature);
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::MinHash;
+    use proptest::{collection::vec, prop_assert_eq};
+    use test_strategy::proptest;
+
+    #[proptest(cases = 100)]
+    fn merge_equals_signature_of_union(
+        #[strategy(vec(proptest::num::u32::ANY, 0..100))] a: Vec<u32>,
+        #[strategy(vec(proptest::num::u32::ANY, 0..100))] b: Vec<u32>,
+    ) {
+        let mut signature_a = MinHash::new(32);
+        let mut signature_b = MinHash::new(32);
+        let mut signature_union = MinHash::new(32);
+
+        for item in a.iter() {
+            signature_a.insert(&item.to_le_bytes());
+            signature_union.insert(&item.to_le_bytes());
+        }
+        for item in b.iter() {
+            signature_b.insert(&item.to_le_bytes());
+            signature_union.insert(&item.to_le_bytes());
+        }
+
+        signature_a.merge(&signature_b).unwrap();
+        prop_assert_eq!(&signature_a, &signature_union);
+        prop_assert_eq!(
+            MinHash::from_bytes(&signature_a.to_bytes()).unwrap(),
+            signature_a
+        );
+    }
+}
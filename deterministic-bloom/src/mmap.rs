@@ -0,0 +1,235 @@
+use crate::{
+    common::{BloomParams, Error, HashIndexIterator},
+    container::{Container, HEADER_LEN, KIND_RUNTIME_SIZE},
+    runtime_size::{BloomFilter, BloomFilterRef},
+};
+use bitvec::{prelude::Lsb0, view::BitView};
+use memmap2::MmapMut;
+use std::{
+    fmt::Debug,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A bloom filter that lives in a memory-mapped file, so it persists across process
+/// restarts without loading or saving all of its bytes.
+///
+/// The file is a [container](crate::runtime_size::BloomFilter::to_container_bytes) of a
+/// [`runtime_size::BloomFilter`](crate::runtime_size::BloomFilter): Its header holds the
+/// filter's parameters, followed by the filter's bytes. So reading the whole file with
+/// [`BloomFilter::from_container_bytes`] gives the same filter, and the same hash
+/// indices are used.
+///
+/// Inserts write straight into the mapping. The operating system writes changes back to
+/// the file eventually, [`flush`](MmapBloomFilter::flush) does so synchronously.
+///
+/// The header is only ever written when creating the file, and the file is only moved
+/// into place once its header is durably written, so a crash never leaves a file with
+/// a partial header behind. As bloom filters only ever set bits, a crash between two
+/// flushes can only lose inserts since the last flush, never corrupt the filter.
+///
+/// Like with any memory-mapped file, other processes must not truncate or modify the
+/// file while it's mapped.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{common::BloomParams, mmap::MmapBloomFilter};
+///
+/// let path = std::env::temp_dir().join(format!("doctest-{}.bloom", std::process::id()));
+/// let params = BloomParams::new_from_fpr(1_000, 0.001);
+///
+/// let mut filter = MmapBloomFilter::create(&path, &params).unwrap();
+/// filter.insert(b"Hello");
+/// filter.flush().unwrap();
+/// drop(filter);
+///
+/// let filter = MmapBloomFilter::open(&path).unwrap();
+/// assert!(filter.contains(b"Hello"));
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct MmapBloomFilter {
+    k_hashes: usize,
+    mmap: MmapMut,
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl MmapBloomFilter {
+    /// Create an empty filter with given parameters in a new file at `path`, replacing
+    /// any file that was there before.
+    ///
+    /// The file is created next to `path` under a temporary name and renamed once its
+    /// header is written and synced, so `path` either holds the previous file or the
+    /// complete new one.
+    pub fn create(path: impl AsRef<Path>, params: &BloomParams) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let temp_path = temp_path(path);
+        let header =
+            Container::encode_header(KIND_RUNTIME_SIZE, params.k_hashes, params.byte_size)?;
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp_path)?;
+        file.write_all(&header)?;
+        file.set_len((HEADER_LEN + params.byte_size) as u64)?;
+        file.sync_all()?;
+
+        fs::rename(&temp_path, path)?;
+        sync_parent_dir(path)?;
+
+        Self::map(&file)
+    }
+
+    /// Open a filter that was created with [`create`](MmapBloomFilter::create) before.
+    ///
+    /// Fails if the file isn't a valid container of a runtime-size filter.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        Self::map(&file)
+    }
+
+    fn map(file: &File) -> Result<Self, Error> {
+        // SAFETY: The mapping is only sound as long as no other process truncates or
+        // modifies the file concurrently, which is documented as a requirement of this type.
+        let mmap = unsafe { MmapMut::map_mut(file)? };
+        let k_hashes = Container::decode(KIND_RUNTIME_SIZE, &mmap)?.k_hashes;
+        Ok(Self { k_hashes, mmap })
+    }
+
+    /// Insert an element into the filter, writing straight into the mapping.
+    ///
+    /// See [`BloomFilter::insert`].
+    pub fn insert(&mut self, item: &impl AsRef<[u8]>) {
+        let bits = self.mmap[HEADER_LEN..].view_bits_mut::<Lsb0>();
+        for i in HashIndexIterator::new(item, bits.len()).take(self.k_hashes) {
+            bits.set(i, true);
+        }
+    }
+
+    /// Check whether an element was (probably) added into the filter.
+    ///
+    /// See [`BloomFilter::contains`].
+    pub fn contains(&self, item: &impl AsRef<[u8]>) -> bool {
+        self.as_filter_ref().contains(item)
+    }
+
+    /// Counts the amount of bits set in the filter.
+    pub fn count_ones(&self) -> usize {
+        self.as_filter_ref().count_ones()
+    }
+
+    /// Compute the bloom parameters of this filter.
+    pub fn get_bloom_params(&self) -> BloomParams {
+        self.as_filter_ref().get_bloom_params()
+    }
+
+    /// Synchronously write all changes to the file.
+    pub fn flush(&self) -> Result<(), Error> {
+        Ok(self.mmap.flush()?)
+    }
+
+    /// Borrow the mapped bytes as a [`BloomFilterRef`], e.g. for set relations with other filters.
+    pub fn as_filter_ref(&self) -> BloomFilterRef<'_> {
+        BloomFilterRef::new(self.k_hashes, &self.mmap[HEADER_LEN..])
+    }
+
+    /// Copy the mapped bytes into an in-memory [`BloomFilter`].
+    pub fn to_filter(&self) -> BloomFilter {
+        self.as_filter_ref().to_filter()
+    }
+}
+
+impl Debug for MmapBloomFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MmapBloomFilter")
+            .field("k_hashes", &self.k_hashes)
+            .field("byte_size", &(self.mmap.len() - HEADER_LEN))
+            .finish()
+    }
+}
+
+/// Returns the path a new filter file is prepared at before it's moved to `path`.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".tmp");
+    path.with_file_name(file_name)
+}
+
+/// Makes sure a rename into the directory of `path` is durable.
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> Result<(), Error> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()?;
+    Ok(())
+}
+
+/// Syncing directories isn't supported on this platform.
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("{name}-{}.bloom", std::process::id()))
+    }
+
+    #[test]
+    fn files_are_runtime_size_containers() {
+        let path = test_path("containers");
+        let params = BloomParams::new_from_fpr(100, 0.001);
+
+        let mut filter = MmapBloomFilter::create(&path, &params).unwrap();
+        let mut expected = BloomFilter::new_from_fpr(100, 0.001);
+        for i in 0u32..100 {
+            filter.insert(&i.to_le_bytes());
+            expected.insert(&i.to_le_bytes());
+        }
+        filter.flush().unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        assert_eq!(BloomFilter::from_container_bytes(&bytes).unwrap(), expected);
+        assert_eq!(filter.to_filter(), expected);
+        assert!(!temp_path(&path).exists());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invalid_files_are_rejected() {
+        let path = test_path("invalid");
+        fs::write(&path, b"not a filter, but long enough for a header").unwrap();
+
+        assert!(matches!(
+            MmapBloomFilter::open(&path),
+            Err(Error::InvalidMagic)
+        ));
+        fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            MmapBloomFilter::open(&path),
+            Err(Error::Io { .. })
+        ));
+    }
+}
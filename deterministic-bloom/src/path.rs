@@ -0,0 +1,194 @@
+use crate::runtime_size::BloomFilter;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A bloom filter for hierarchical, filesystem-like paths.
+///
+/// Inserting a path like `/a/b/c` records the path itself as well as each of its
+/// ancestors `/`, `/a` and `/a/b`, so that it's possible to ask both whether a path
+/// was inserted exactly and whether anything was inserted below some directory.
+///
+/// Exact paths and ancestors are hashed with different domain separation tags, and
+/// every path segment is length-prefixed before hashing. This way `/ab` and `/a/b`
+/// (or `/a/b` as exact path and `/a/b` as ancestor) never produce the same item.
+///
+/// Paths given as strings are split on `/`, and empty segments are ignored, i.e.
+/// `/a//b/` is the same path as `/a/b`.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::path::PathFilter;
+///
+/// let mut filter = PathFilter::new_from_fpr(100, 0.0001);
+/// filter.insert("/photos/2023/beach.jpg");
+///
+/// assert!(filter.contains_path("/photos/2023/beach.jpg"));
+/// assert!(!filter.contains_path("/photos/2023"));
+///
+/// assert!(filter.may_contain_descendant_of("/photos"));
+/// assert!(filter.may_contain_descendant_of("/photos/2023"));
+/// assert!(!filter.may_contain_descendant_of("/photos/2023/beach.jpg"));
+/// assert!(!filter.may_contain_descendant_of("/documents"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathFilter {
+    filter: BloomFilter,
+}
+
+/// Domain separation tag for items representing an inserted path.
+const EXACT_TAG: u8 = 0;
+
+/// Domain separation tag for items representing an ancestor of an inserted path.
+const ANCESTOR_TAG: u8 = 1;
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl PathFilter {
+    /// Construct a path filter with optimal parameters for `n_elems` items and false
+    /// positive rate `fpr`.
+    ///
+    /// Note that every inserted path of depth `d` adds up to `d + 1` items: the path
+    /// itself and each of its ancestors. Ancestors shared between paths only count once.
+    pub fn new_from_fpr(n_elems: u64, fpr: f64) -> Self {
+        Self::from_filter(BloomFilter::new_from_fpr(n_elems, fpr))
+    }
+
+    /// Wrap an existing bloom filter, e.g. one that was deserialized.
+    pub fn from_filter(filter: BloomFilter) -> Self {
+        Self { filter }
+    }
+
+    /// Insert a `/`-separated path, together with all of its ancestors.
+    pub fn insert(&mut self, path: &str) {
+        self.insert_segments(&split_path(path));
+    }
+
+    /// Insert a path given as list of segments, together with all of its ancestors.
+    pub fn insert_segments(&mut self, segments: &[impl AsRef<[u8]>]) {
+        for depth in 0..segments.len() {
+            self.filter
+                .insert(&encode(ANCESTOR_TAG, &segments[..depth]));
+        }
+
+        self.filter.insert(&encode(EXACT_TAG, segments));
+    }
+
+    /// Check whether a `/`-separated path was (probably) inserted.
+    ///
+    /// This doesn't match paths that were only inserted as ancestors of other paths.
+    pub fn contains_path(&self, path: &str) -> bool {
+        self.contains_segments(&split_path(path))
+    }
+
+    /// Check whether a path given as list of segments was (probably) inserted.
+    pub fn contains_segments(&self, segments: &[impl AsRef<[u8]>]) -> bool {
+        self.filter.contains(&encode(EXACT_TAG, segments))
+    }
+
+    /// Check whether any path strictly below the `/`-separated `path` was (probably) inserted.
+    pub fn may_contain_descendant_of(&self, path: &str) -> bool {
+        self.may_contain_descendant_of_segments(&split_path(path))
+    }
+
+    /// Check whether any path strictly below the path given as list of segments was
+    /// (probably) inserted.
+    pub fn may_contain_descendant_of_segments(&self, segments: &[impl AsRef<[u8]>]) -> bool {
+        self.filter.contains(&encode(ANCESTOR_TAG, segments))
+    }
+
+    /// Returns the underlying bloom filter.
+    pub fn as_filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+}
+
+impl From<PathFilter> for BloomFilter {
+    fn from(path_filter: PathFilter) -> Self {
+        path_filter.filter
+    }
+}
+
+/// Splits a path on `/`, skipping empty segments.
+fn split_path(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect()
+}
+
+/// Encodes a tagged path as bytes, prefixing each segment with its length.
+fn encode(tag: u8, segments: &[impl AsRef<[u8]>]) -> Vec<u8> {
+    let mut bytes = vec![tag];
+    for segment in segments {
+        let segment = segment.as_ref();
+        bytes.extend_from_slice(&(segment.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(segment);
+    }
+    bytes
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_boundaries_are_unambiguous() {
+        assert_ne!(encode(EXACT_TAG, &["ab"]), encode(EXACT_TAG, &["a", "b"]));
+        assert_ne!(
+            encode(EXACT_TAG, &["a", "b"]),
+            encode(ANCESTOR_TAG, &["a", "b"])
+        );
+    }
+
+    #[test]
+    fn root_is_ancestor_of_everything() {
+        let mut filter = PathFilter::new_from_fpr(10, 0.0001);
+        assert!(!filter.may_contain_descendant_of("/"));
+
+        filter.insert("/a");
+        assert!(filter.may_contain_descendant_of("/"));
+        assert!(!filter.contains_path("/"));
+    }
+
+    #[test]
+    fn empty_segments_are_ignored() {
+        let mut filter = PathFilter::new_from_fpr(10, 0.0001);
+        filter.insert("a//b/");
+
+        assert!(filter.contains_path("/a/b"));
+        assert!(filter.contains_segments(&["a", "b"]));
+        assert!(filter.may_contain_descendant_of_segments(&["a"]));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::PathFilter;
+    use proptest::{collection::vec, prop_assert};
+    use test_strategy::proptest;
+
+    #[proptest]
+    fn inserted_paths_and_ancestors_are_found(
+        #[strategy(vec(vec("[a-c]{1,3}", 0..5), 1..20))] paths: Vec<Vec<String>>,
+    ) {
+        let mut filter = PathFilter::new_from_fpr(100, 0.01);
+        for path in paths.iter() {
+            filter.insert(&format!("/{}", path.join("/")));
+        }
+
+        for path in paths.iter() {
+            prop_assert!(filter.contains_segments(path));
+            for depth in 0..path.len() {
+                prop_assert!(filter.may_contain_descendant_of_segments(&path[..depth]));
+            }
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use crate::runtime_size::BloomFilter;
+use bitvec::{prelude::Lsb0, view::BitView};
+use rand_core::RngCore;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A bloom filter that was released with `epsilon`-differential privacy, following
+/// [BLIP] and [RAPPOR]'s permanent randomized response.
+///
+/// Every bit of the original filter was flipped independently with probability
+/// `1 / (1 + e^(epsilon / k))`. Since each item affects at most `k` bits, this hides
+/// the presence of any single item with `epsilon`-differential privacy for the whole
+/// filter. That's a formal guarantee even against peers with unbounded auxiliary
+/// knowledge, as long as the same filter isn't privatized repeatedly with fresh noise.
+///
+/// Queries on the noised bits directly are meaningless, so this type offers estimators
+/// that correct for the known flip probability instead.
+///
+/// Construct it with [`runtime_size::BloomFilter::privatize`](crate::runtime_size::BloomFilter::privatize)
+/// or [`const_size::BloomFilter::privatize`](crate::const_size::BloomFilter::privatize).
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::runtime_size::BloomFilter;
+/// use rand::{rngs::StdRng, SeedableRng};
+///
+/// let mut filter = BloomFilter::new_from_fpr(1_000, 0.001);
+/// for i in 0u32..500 {
+///     filter.insert(&i.to_le_bytes());
+/// }
+///
+/// let private = filter.privatize(20.0, &mut StdRng::seed_from_u64(0));
+///
+/// let estimate = private.estimate_cardinality();
+/// assert!((estimate - 500.0).abs() < 50.0);
+///
+/// assert!(private.likely_contains(&1u32.to_le_bytes()));
+/// assert!(!private.likely_contains(&1_000u32.to_le_bytes()));
+/// ```
+///
+/// [BLIP]: https://doi.org/10.1109/ICDCS.2011.58
+/// [RAPPOR]: https://arxiv.org/abs/1407.6981
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrivatizedBloomFilter {
+    epsilon: f64,
+    noised: BloomFilter,
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl PrivatizedBloomFilter {
+    /// Construct a privatized filter from the noised filter and the `epsilon`
+    /// it was privatized with, e.g. when receiving it from a peer.
+    pub fn new_with(epsilon: f64, noised: BloomFilter) -> Self {
+        Self { epsilon, noised }
+    }
+
+    /// Flip each bit of a filter with `k_hashes` hash functions and given bits.
+    pub(crate) fn privatize(
+        k_hashes: usize,
+        bytes: &[u8],
+        epsilon: f64,
+        rng: &mut impl RngCore,
+    ) -> Self {
+        debug_assert!(epsilon > 0.0);
+
+        let p = flip_probability(epsilon, k_hashes);
+        let mut bytes = Box::<[u8]>::from(bytes);
+        for mut bit in bytes.view_bits_mut::<Lsb0>().iter_mut() {
+            if uniform(rng) < p {
+                *bit = !*bit;
+            }
+        }
+
+        Self::new_with(epsilon, BloomFilter::new_with(k_hashes, bytes))
+    }
+
+    /// Returns the privacy parameter this filter was privatized with.
+    pub fn epsilon(&self) -> f64 {
+        self.epsilon
+    }
+
+    /// Returns the probability with which each bit was flipped.
+    pub fn flip_probability(&self) -> f64 {
+        flip_probability(self.epsilon, self.noised.hash_count())
+    }
+
+    /// Returns the noised filter.
+    ///
+    /// Note that [`contains`](BloomFilter::contains) on the noised filter has a
+    /// high false negative rate. Use [`likely_contains`](PrivatizedBloomFilter::likely_contains)
+    /// instead.
+    pub fn as_filter(&self) -> &BloomFilter {
+        &self.noised
+    }
+
+    /// Estimate the number of bits that were set in the original filter.
+    ///
+    /// The estimate is unbiased, but not clamped to the valid range, so for sparse
+    /// or almost full filters it may be negative or exceed the filter's size.
+    pub fn estimate_count_ones(&self) -> f64 {
+        let m = (self.noised.as_bytes().len() * 8) as f64;
+        let p = self.flip_probability();
+        (self.noised.count_ones() as f64 - m * p) / (1.0 - 2.0 * p)
+    }
+
+    /// Estimate the number of distinct items in the original filter.
+    ///
+    /// Returns infinity if the original filter was likely saturated.
+    pub fn estimate_cardinality(&self) -> f64 {
+        let m = (self.noised.as_bytes().len() * 8) as f64;
+        let k = self.noised.hash_count() as f64;
+        let fill = self.estimated_fill_ratio();
+        -(m / k) * (1.0 - fill).ln()
+    }
+
+    /// Estimate the fraction of the item's bits that were set in the original filter.
+    ///
+    /// This is unbiased, so it averages to `1.0` for items of the original filter.
+    pub fn membership_score(&self, item: &impl AsRef<[u8]>) -> f64 {
+        let (set, total) = self.count_set_indices(item);
+        if total == 0 {
+            return 0.0;
+        }
+
+        let p = self.flip_probability();
+        (set as f64 / total as f64 - p) / (1.0 - 2.0 * p)
+    }
+
+    /// Decide whether the item was more likely part of the original filter than not.
+    ///
+    /// This compares the likelihood of the item's noised bits if it was inserted, in which
+    /// case each of them was set before flipping, to the likelihood if it wasn't, in which
+    /// case each of them was set with the original filter's estimated fill ratio.
+    pub fn likely_contains(&self, item: &impl AsRef<[u8]>) -> bool {
+        let (set, total) = self.count_set_indices(item);
+        let unset = (total - set) as i32;
+        let set = set as i32;
+
+        let p = self.flip_probability();
+        let fill = self.estimated_fill_ratio();
+        let q = fill * (1.0 - p) + (1.0 - fill) * p;
+
+        let member = (1.0 - p).powi(set) * p.powi(unset);
+        let non_member = q.powi(set) * (1.0 - q).powi(unset);
+        member > non_member
+    }
+
+    /// Returns the estimated fraction of set bits in the original filter, between 0 and 1.
+    fn estimated_fill_ratio(&self) -> f64 {
+        let m = (self.noised.as_bytes().len() * 8) as f64;
+        if m == 0.0 {
+            return 0.0;
+        }
+
+        (self.estimate_count_ones() / m).clamp(0.0, 1.0)
+    }
+
+    /// Returns how many of the item's hash indices are set in the noised filter, and
+    /// how many hash indices there are.
+    fn count_set_indices(&self, item: &impl AsRef<[u8]>) -> (usize, usize) {
+        let bits = self.noised.as_bytes().view_bits::<Lsb0>();
+        self.noised
+            .hash_indices(item)
+            .fold((0, 0), |(set, total), i| {
+                (set + bits[i] as usize, total + 1)
+            })
+    }
+}
+
+/// Returns the probability with which bits of a filter with `k_hashes` hash functions
+/// are flipped for `epsilon`-differential privacy.
+pub fn flip_probability(epsilon: f64, k_hashes: usize) -> f64 {
+    1.0 / (1.0 + (epsilon / k_hashes as f64).exp())
+}
+
+/// Returns a uniformly distributed number in `[0, 1)`.
+fn uniform(rng: &mut impl RngCore) -> f64 {
+    (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn flip_probability_matches_randomized_response() {
+        assert_eq!(flip_probability(0.0, 1), 0.5);
+        assert!((flip_probability(2.0f64.ln(), 1) - 1.0 / 3.0).abs() < 1e-12);
+        assert!((flip_probability(4.0 * 2.0f64.ln(), 4) - 1.0 / 3.0).abs() < 1e-12);
+        assert_eq!(flip_probability(f64::INFINITY, 4), 0.0);
+    }
+
+    #[test]
+    fn noise_is_deterministic_given_rng() {
+        let mut filter = BloomFilter::new_from_fpr(100, 0.01);
+        filter.insert(b"Hello");
+
+        let a = filter.privatize(1.0, &mut StdRng::seed_from_u64(3));
+        let b = filter.privatize(1.0, &mut StdRng::seed_from_u64(3));
+        assert_eq!(a, b);
+        assert_ne!(a.as_filter(), &filter);
+    }
+
+    #[test]
+    fn const_size_filters_privatize_like_runtime_size_filters() {
+        let mut filter = crate::const_size::BloomFilter::<256, 8>::new();
+        filter.insert(b"Hello");
+        let runtime = BloomFilter::new_with(8, Box::from(filter.as_bytes()));
+
+        let a = filter.privatize(1.0, &mut StdRng::seed_from_u64(3));
+        let b = runtime.privatize(1.0, &mut StdRng::seed_from_u64(3));
+        assert_eq!(a, b);
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use crate::runtime_size::BloomFilter;
+    use proptest::prop_assert;
+    use rand::{rngs::StdRng, SeedableRng};
+    use test_strategy::proptest;
+
+    #[proptest(cases = 50)]
+    fn cardinality_estimate_is_close(#[strategy(100u32..1000)] n: u32, seed: u64) {
+        let mut filter = BloomFilter::new_from_fpr(1000, 0.01);
+        for i in 0..n {
+            filter.insert(&i.to_le_bytes());
+        }
+
+        let private = filter.privatize(20.0, &mut StdRng::seed_from_u64(seed));
+        let estimate = private.estimate_cardinality();
+        prop_assert!((estimate - n as f64).abs() < 0.15 * n as f64);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{common::BloomParams, runtime_size::BloomFilter};
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// A filter that answers whether any key in a range `[lo, hi]` was (probably) inserted.
+///
+/// This follows the design of [Rosetta]: there is one [`BloomFilter`] per prefix length,
+/// and inserting a key inserts each of its bit prefixes into the filter of that length.
+/// A range query descends the implicit binary trie of prefixes, only following
+/// prefixes that intersect the range and that the filters claim exist, until it
+/// reaches a full-length key.
+///
+/// Keys are fixed-width byte strings of `key_bytes` bytes, compared lexicographically.
+/// This covers big-endian encoded integers as well as byte strings: longer keys are
+/// truncated and shorter keys are padded with zeros. Since that mapping preserves
+/// ordering, there are no false negatives, but keys that only differ after
+/// `key_bytes` bytes can't be told apart.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::range::RangeFilter;
+///
+/// let mut filter = RangeFilter::new(8, 1000, 0.001);
+///
+/// for i in 0u64..1000 {
+///     filter.insert(&(i * 1000).to_be_bytes());
+/// }
+///
+/// assert!(filter.contains_range(&4500u64.to_be_bytes(), &5500u64.to_be_bytes()));
+/// assert!(!filter.contains_range(&5001u64.to_be_bytes(), &5999u64.to_be_bytes()));
+/// ```
+///
+/// [Rosetta]: https://dl.acm.org/doi/10.1145/3318464.3389731
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeFilter {
+    key_bytes: usize,
+    /// `levels[i]` holds all key prefixes of length `i + 1` bits
+    levels: Vec<BloomFilter>,
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl RangeFilter {
+    /// Construct a range filter for keys of `key_bytes` bytes, with capacity `n_elems`
+    /// and the false positive rate `fpr` for point queries.
+    ///
+    /// Each level's filter is sized via [`BloomParams::new_from_fpr`] for the number of
+    /// distinct prefixes it can hold at most, so the short prefix levels stay small.
+    ///
+    /// `key_bytes` and `n_elems` must be non-zero and `fpr` must be a number between 0 and 1 exclusive.
+    pub fn new(key_bytes: usize, n_elems: u64, fpr: f64) -> Self {
+        debug_assert!(key_bytes != 0);
+
+        let levels = (1..=key_bytes * 8)
+            .map(|prefix_len| {
+                let distinct_prefixes = 1u64.checked_shl(prefix_len as u32).unwrap_or(u64::MAX);
+                let params = BloomParams::new_from_fpr(n_elems.min(distinct_prefixes), fpr);
+                BloomFilter::new_with(
+                    params.k_hashes,
+                    vec![0u8; params.byte_size].into_boxed_slice(),
+                )
+            })
+            .collect();
+
+        Self { key_bytes, levels }
+    }
+
+    /// Insert a key into the filter.
+    pub fn insert(&mut self, key: &impl AsRef<[u8]>) {
+        let key = self.normalize(key.as_ref());
+        let mut prefix = vec![0u8; self.key_bytes];
+
+        for (index, level) in self.levels.iter_mut().enumerate() {
+            set_bit(&mut prefix, index, get_bit(&key, index));
+            level.insert(&prefix);
+        }
+    }
+
+    /// Check whether a key was (probably) inserted.
+    pub fn contains(&self, key: &impl AsRef<[u8]>) -> bool {
+        self.contains_range(key, key)
+    }
+
+    /// Check whether any key between `lo` and `hi` (both inclusive) was (probably) inserted.
+    ///
+    /// This always returns true if such a key was inserted, but may also return true
+    /// if none was. Returns false if `lo > hi`.
+    pub fn contains_range(&self, lo: &impl AsRef<[u8]>, hi: &impl AsRef<[u8]>) -> bool {
+        let lo = self.normalize(lo.as_ref());
+        let hi = self.normalize(hi.as_ref());
+
+        if lo > hi {
+            return false;
+        }
+
+        self.descend(&mut vec![0u8; self.key_bytes], 0, &lo, &hi)
+    }
+
+    /// Returns the width of keys in bytes.
+    pub fn key_bytes(&self) -> usize {
+        self.key_bytes
+    }
+
+    /// Returns the filters for each prefix length, starting with 1-bit prefixes.
+    pub fn levels(&self) -> &[BloomFilter] {
+        &self.levels
+    }
+
+    /// Returns the total size of all levels in bytes.
+    pub fn byte_size(&self) -> usize {
+        self.levels.iter().map(|level| level.as_bytes().len()).sum()
+    }
+
+    /// Checks whether the trie node `prefix` of length `prefix_len` bits (all bits
+    /// after it unset) or any of its descendants intersect `[lo, hi]` and are present.
+    fn descend(&self, prefix: &mut [u8], prefix_len: usize, lo: &[u8], hi: &[u8]) -> bool {
+        let node_hi = fill_ones_from(prefix, prefix_len);
+        if node_hi.as_slice() < lo || &*prefix > hi {
+            return false;
+        }
+
+        if prefix_len > 0 && !self.levels[prefix_len - 1].contains(&prefix) {
+            return false;
+        }
+
+        if prefix_len == self.levels.len() {
+            return true;
+        }
+
+        if self.descend(prefix, prefix_len + 1, lo, hi) {
+            return true;
+        }
+
+        set_bit(prefix, prefix_len, true);
+        let found = self.descend(prefix, prefix_len + 1, lo, hi);
+        set_bit(prefix, prefix_len, false);
+        found
+    }
+
+    /// Truncates or zero-pads a key to `key_bytes` bytes.
+    fn normalize(&self, key: &[u8]) -> Vec<u8> {
+        let mut normalized = vec![0u8; self.key_bytes];
+        let len = key.len().min(self.key_bytes);
+        normalized[..len].copy_from_slice(&key[..len]);
+        normalized
+    }
+}
+
+/// Returns the bit at `index`, counting from the most significant bit of the first byte.
+fn get_bit(bytes: &[u8], index: usize) -> bool {
+    bytes[index / 8] & (0x80 >> (index % 8)) != 0
+}
+
+/// Sets the bit at `index`, counting from the most significant bit of the first byte.
+fn set_bit(bytes: &mut [u8], index: usize, value: bool) {
+    let mask = 0x80 >> (index % 8);
+    if value {
+        bytes[index / 8] |= mask;
+    } else {
+        bytes[index / 8] &= !mask;
+    }
+}
+
+/// Returns a copy of `bytes` with all bits from `index` onwards set.
+fn fill_ones_from(bytes: &[u8], index: usize) -> Vec<u8> {
+    let mut filled = bytes.to_vec();
+    for i in index..bytes.len() * 8 {
+        set_bit(&mut filled, i, true);
+    }
+    filled
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::RangeFilter;
+
+    #[test]
+    fn empty_filter_contains_nothing() {
+        let filter = RangeFilter::new(4, 100, 0.01);
+        assert!(!filter.contains_range(&[0u8; 4], &[0xFFu8; 4]));
+    }
+
+    #[test]
+    fn inverted_range_is_empty() {
+        let mut filter = RangeFilter::new(4, 100, 0.01);
+        filter.insert(&5u32.to_be_bytes());
+        assert!(!filter.contains_range(&10u32.to_be_bytes(), &0u32.to_be_bytes()));
+    }
+
+    #[test]
+    fn byte_string_keys_are_compared_lexicographically() {
+        let mut filter = RangeFilter::new(6, 100, 0.001);
+        filter.insert(b"banana");
+        filter.insert(b"cherry");
+
+        assert!(filter.contains_range(b"b", b"c"));
+        assert!(filter.contains_range(b"ch", b"ci"));
+        assert!(!filter.contains_range(b"d", b"z"));
+        assert!(!filter.contains_range(b"a", b"az"));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::RangeFilter;
+    use proptest::{collection::vec, prop_assert};
+    use test_strategy::proptest;
+
+    #[proptest(cases = 200)]
+    fn no_false_negatives(
+        #[strategy(vec(proptest::num::u16::ANY, 1..50))] keys: Vec<u16>,
+        a: u16,
+        b: u16,
+    ) {
+        let (lo, hi) = (a.min(b), a.max(b));
+        let mut filter = RangeFilter::new(2, keys.len() as u64, 0.01);
+        for key in keys.iter() {
+            filter.insert(&key.to_be_bytes());
+        }
+
+        for key in keys.iter() {
+            prop_assert!(filter.contains(&key.to_be_bytes()));
+        }
+
+        if keys.iter().any(|key| (lo..=hi).contains(key)) {
+            prop_assert!(filter.contains_range(&lo.to_be_bytes(), &hi.to_be_bytes()));
+        }
+    }
+}
@@ -1,8 +1,12 @@
 use crate::{
-    common::{BloomParams, HashIndexIterator},
-    utils::HexFieldDebug,
+    common::{BloomParams, Error, HashIndexIterator},
+    container::{Container, KIND_RUNTIME_SIZE},
+    privacy::PrivatizedBloomFilter,
+    utils::{DeserializeBytes, HexFieldDebug, SerializeBytes},
 };
 use bitvec::{prelude::Lsb0, view::BitView};
+use rand_core::RngCore;
+use serde::{de::Error as _, Deserialize, Serialize};
 use std::fmt::Debug;
 
 //------------------------------------------------------------------------------
@@ -31,12 +35,41 @@ use std::fmt::Debug;
 /// ```
 ///
 /// [bloom filter]: https://en.wikipedia.org/wiki/Bloom_filter
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Eq, PartialOrd, Ord)]
+#[cfg_attr(not(feature = "constant_time"), derive(PartialEq))]
 pub struct BloomFilter {
     k_hashes: usize,
     bytes: Box<[u8]>,
 }
 
+/// A zero-copy, read-only view of a [`BloomFilter`]'s bits, borrowed from some byte slice.
+///
+/// This is useful for querying filters that live inside larger buffers, e.g. memory-mapped
+/// files or received messages, without copying their bytes. It uses the same hashing as
+/// [`BloomFilter`], so a view over a filter's bytes gives the same answers as the filter.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::runtime_size::{BloomFilter, BloomFilterRef};
+///
+/// let mut filter = BloomFilter::new_from_fpr(100, 0.001);
+/// filter.insert(b"Hello");
+///
+/// // E.g. a message with a header, followed by the filter's bytes
+/// let mut message = b"header".to_vec();
+/// message.extend_from_slice(filter.as_bytes());
+///
+/// let view = BloomFilterRef::new(filter.hash_count(), &message[6..]);
+/// assert!(view.contains(b"Hello"));
+/// assert!(view.is_subset_of(&filter.as_filter_ref()).unwrap());
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct BloomFilterRef<'a> {
+    k_hashes: usize,
+    bytes: &'a [u8],
+}
+
 impl BloomFilter {
     /// Construct a bloom filter with optimal parameters for given maximum capacity `n_elems`
     /// and false positive rate `fpr`.
@@ -141,6 +174,27 @@ impl BloomFilter {
         Self { k_hashes, bytes }
     }
 
+    /// Construct the bloom filter from its [compressed](crate::compressed::compress) bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::runtime_size::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::new_from_fpr(10_000, 0.01);
+    /// filter.insert(b"Hello, World!");
+    ///
+    /// let compressed = filter.to_compressed_bytes();
+    /// assert!(compressed.len() < 100);
+    ///
+    /// let decompressed = BloomFilter::from_compressed_bytes(filter.hash_count(), &compressed).unwrap();
+    /// assert_eq!(decompressed, filter);
+    /// ```
+    pub fn from_compressed_bytes(k_hashes: usize, compressed: &[u8]) -> Result<Self, Error> {
+        let bytes = crate::compressed::decompress(compressed)?;
+        Ok(Self::new_with(k_hashes, bytes.into_boxed_slice()))
+    }
+
     /// Compute the bloom parameters for this bloom filter.
     /// This contains information about its size and hash function evaluations per
     /// item (`k_hashes`).
@@ -242,6 +296,153 @@ impl BloomFilter {
         true
     }
 
+    /// Check whether an element was added into the bloom filter, in constant time.
+    ///
+    /// Unlike [`contains`](BloomFilter::contains), this always reads all `k` bits of
+    /// the item, and combines them without branching, so the time it takes doesn't
+    /// depend on which of them are set. Computing the bit indices itself uses rejection
+    /// sampling for filters with non-power-of-two sizes, which depends on the item only.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::runtime_size::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::new_from_fpr(100, 0.001);
+    /// filter.insert(b"secret name");
+    ///
+    /// assert!(bool::from(filter.ct_contains(b"secret name")));
+    /// assert!(!bool::from(filter.ct_contains(b"other name")));
+    /// ```
+    #[cfg(feature = "constant_time")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "constant_time")))]
+    pub fn ct_contains(&self, item: &impl AsRef<[u8]>) -> subtle::Choice {
+        let mut found = subtle::Choice::from(1);
+        for i in self.hash_indices(item) {
+            found &= subtle::Choice::from((self.bytes[i / 8] >> (i % 8)) & 1);
+        }
+        found
+    }
+
+    /// Add all items of `other` to this bloom filter by OR-ing their bits.
+    ///
+    /// Both filters need to have the same parameters, otherwise this returns
+    /// an error and leaves this filter unchanged.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::runtime_size::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::new_from_fpr(100, 0.001);
+    /// let mut other = BloomFilter::new_from_fpr(100, 0.001);
+    /// filter.insert(b"Hello");
+    /// other.insert(b"World!");
+    ///
+    /// filter.union_with(&other).unwrap();
+    ///
+    /// assert!(filter.contains(b"Hello"));
+    /// assert!(filter.contains(b"World!"));
+    /// ```
+    pub fn union_with(&mut self, other: &BloomFilter) -> Result<(), Error> {
+        if self.get_bloom_params() != other.get_bloom_params() {
+            return Err(Error::BloomParamsMismatch {
+                expected: self.get_bloom_params(),
+                actual: other.get_bloom_params(),
+            });
+        }
+
+        for (byte, other_byte) in self.bytes.iter_mut().zip(other.bytes.iter()) {
+            *byte |= other_byte;
+        }
+
+        Ok(())
+    }
+
+    /// Shrink a filter with a power-of-two size by `factor`, a power of two as well, by
+    /// OR-ing its parts together.
+    ///
+    /// For power-of-two sizes, the hash indices are hashes modulo the bit size without
+    /// rejection sampling, so an index modulo the smaller size is the index the item gets
+    /// in the smaller filter. Folding thus gives exactly the filter that inserting the same
+    /// items into a filter of the smaller size would give, with its higher false positive
+    /// rate.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::runtime_size::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::new_from_fpr_po2(1_000, 0.001);
+    /// filter.insert(b"Hello");
+    ///
+    /// let folded = filter.fold(4).unwrap();
+    /// assert_eq!(folded.as_bytes().len(), filter.as_bytes().len() / 4);
+    /// assert!(folded.contains(b"Hello"));
+    /// ```
+    pub fn fold(&self, factor: usize) -> Result<BloomFilter, Error> {
+        let byte_size = self.bytes.len();
+        if !byte_size.is_power_of_two() || !factor.is_power_of_two() || factor > byte_size {
+            return Err(Error::InvalidFoldFactor { factor, byte_size });
+        }
+
+        let folded_size = byte_size / factor;
+        let mut bytes = self.bytes[..folded_size].to_vec();
+        for part in self.bytes.chunks(folded_size).skip(1) {
+            for (byte, other_byte) in bytes.iter_mut().zip(part.iter()) {
+                *byte |= other_byte;
+            }
+        }
+
+        Ok(Self {
+            k_hashes: self.k_hashes,
+            bytes: bytes.into_boxed_slice(),
+        })
+    }
+
+    /// Like [`union_with`](BloomFilter::union_with), but also combines filters of different
+    /// power-of-two sizes by [folding](BloomFilter::fold) the larger one down to the size
+    /// of the smaller one first.
+    ///
+    /// Fails if the filters use different numbers of hash functions, or have different sizes
+    /// that aren't both powers of two.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::runtime_size::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::new_from_fpr_po2(1_000, 0.01);
+    /// let mut other = BloomFilter::new_with(filter.hash_count(), Box::new([0; 8192]));
+    /// filter.insert(b"Hello");
+    /// other.insert(b"World!");
+    ///
+    /// filter.union_with_folding(&other).unwrap();
+    ///
+    /// assert!(filter.contains(b"Hello"));
+    /// assert!(filter.contains(b"World!"));
+    /// ```
+    pub fn union_with_folding(&mut self, other: &BloomFilter) -> Result<(), Error> {
+        let (size, other_size) = (self.bytes.len(), other.bytes.len());
+        if self.k_hashes != other.k_hashes
+            || (size != other_size && !(size.is_power_of_two() && other_size.is_power_of_two()))
+        {
+            return Err(Error::BloomParamsMismatch {
+                expected: self.get_bloom_params(),
+                actual: other.get_bloom_params(),
+            });
+        }
+
+        if size == other_size {
+            self.union_with(other)
+        } else if size > other_size {
+            *self = self.fold(size / other_size)?;
+            self.union_with(other)
+        } else {
+            self.union_with(&other.fold(other_size / size)?)
+        }
+    }
+
     /// Returns how many hash function invocations are used pre item inserted
     pub fn hash_count(&self) -> usize {
         self.k_hashes
@@ -252,12 +453,356 @@ impl BloomFilter {
         &self.bytes
     }
 
+    /// Mutably borrow the underlying bloom bits, e.g. for patching them with a delta.
+    pub(crate) fn as_bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+
+    /// Return the bloom bits [compressed](crate::compressed::compress) for transport.
+    pub fn to_compressed_bytes(&self) -> Vec<u8> {
+        crate::compressed::compress(&self.bytes)
+    }
+
+    /// Release a noised copy of this filter with `epsilon`-differential privacy.
+    ///
+    /// Each bit is flipped independently with probability `1 / (1 + e^(epsilon / k))`.
+    /// `epsilon` must be positive. See [`PrivatizedBloomFilter`] for how to query the result.
+    pub fn privatize(&self, epsilon: f64, rng: &mut impl RngCore) -> PrivatizedBloomFilter {
+        PrivatizedBloomFilter::privatize(self.k_hashes, &self.bytes, epsilon, rng)
+    }
+
+    /// Encode this filter into the canonical, self-describing container format for archiving.
+    ///
+    /// Fails if the number of hash functions doesn't fit into 32 bits.
+    ///
+    /// All integers are little-endian:
+    ///
+    /// | bytes | field                                                                  |
+    /// |-------|------------------------------------------------------------------------|
+    /// | 4     | magic number [`MAGIC`](crate::container::MAGIC)                        |
+    /// | 1     | format version, currently [`VERSION`](crate::container::VERSION)       |
+    /// | 1     | filter kind, [`KIND_RUNTIME_SIZE`]                                     |
+    /// | 1     | hash scheme, [`HASH_SCHEME_XXH3`](crate::container::HASH_SCHEME_XXH3)  |
+    /// | 8     | hash seed, currently always `0`                                        |
+    /// | 4     | number of hash functions `k`                                           |
+    /// | 8     | bit length of the filter, a multiple of 8                              |
+    /// | *     | the filter's bytes, exactly `bit length / 8` of them                   |
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::runtime_size::BloomFilter;
+    ///
+    /// let mut filter = BloomFilter::new_from_fpr(100, 0.001);
+    /// filter.insert(b"Hello");
+    ///
+    /// let bytes = filter.to_container_bytes().unwrap();
+    /// assert_eq!(&bytes[..4], b"DBLF");
+    ///
+    /// let decoded = BloomFilter::from_container_bytes(&bytes).unwrap();
+    /// assert_eq!(decoded, filter);
+    /// ```
+    pub fn to_container_bytes(&self) -> Result<Vec<u8>, Error> {
+        Container::encode(KIND_RUNTIME_SIZE, self.k_hashes, &self.bytes)
+    }
+
+    /// Decode a filter from its [container](BloomFilter::to_container_bytes) encoding.
+    ///
+    /// Decoding is strict: Fails if any header field is invalid or unsupported, if the
+    /// container holds a different kind of filter, or if the payload length doesn't
+    /// match the bit length exactly.
+    pub fn from_container_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let container = Container::decode(KIND_RUNTIME_SIZE, bytes)?;
+        Ok(Self::new_with(
+            container.k_hashes,
+            Box::from(container.payload),
+        ))
+    }
+
+    /// Returns the canonical IPLD representation of this filter, see [`SCHEMA`](crate::ipld::SCHEMA).
+    #[cfg(feature = "ipld")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+    pub fn to_ipld(&self) -> libipld::Ipld {
+        crate::ipld::to_ipld(self.k_hashes, &self.bytes)
+    }
+
+    /// Parse a filter from its canonical IPLD representation, see [`SCHEMA`](crate::ipld::SCHEMA).
+    ///
+    /// Fails if the representation doesn't match the schema or `k_hashes` is zero.
+    #[cfg(feature = "ipld")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+    pub fn from_ipld(ipld: &libipld::Ipld) -> Result<Self, Error> {
+        let (k_hashes, bytes) = crate::ipld::from_ipld(ipld)?;
+        Ok(Self::new_with(k_hashes, Box::from(bytes)))
+    }
+
+    /// Compute the CIDv1 of this filter's canonical DAG-CBOR encoding, hashed with given multihash.
+    ///
+    /// Every producer computes the same CID for filters with the same parameters and bits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::runtime_size::BloomFilter;
+    /// use libipld::multihash::Code;
+    ///
+    /// let mut filter = BloomFilter::new_from_fpr(100, 0.001);
+    /// filter.insert(b"Hello");
+    ///
+    /// let cid = filter.cid(Code::Sha2_256);
+    /// assert_eq!(cid.codec(), 0x71); // DAG-CBOR
+    /// ```
+    #[cfg(feature = "ipld")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+    pub fn cid(&self, code: libipld::multihash::Code) -> libipld::Cid {
+        crate::ipld::cid(self.k_hashes, &self.bytes, code)
+    }
+
+    /// Encode this filter's [container](BloomFilter::to_container_bytes) as multibase string
+    /// in given base, e.g. for JSON APIs, query strings or HTTP headers.
+    ///
+    /// Use [`FromStr`](std::str::FromStr) to decode it again.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use deterministic_bloom::runtime_size::BloomFilter;
+    /// use multibase::Base;
+    ///
+    /// let mut filter = BloomFilter::new_from_fpr(100, 0.001);
+    /// filter.insert(b"Hello");
+    ///
+    /// let string = filter.to_multibase_string(Base::Base64Url).unwrap();
+    /// assert!(string.starts_with('u'));
+    ///
+    /// let decoded: BloomFilter = string.parse().unwrap();
+    /// assert_eq!(decoded, filter);
+    /// ```
+    #[cfg(feature = "multibase")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "multibase")))]
+    pub fn to_multibase_string(&self, base: multibase::Base) -> Result<String, Error> {
+        Ok(multibase::encode(base, self.to_container_bytes()?))
+    }
+
+    /// Borrow this filter as a [`BloomFilterRef`].
+    pub fn as_filter_ref(&self) -> BloomFilterRef<'_> {
+        BloomFilterRef::new(self.k_hashes, &self.bytes)
+    }
+
     /// Return the indices that a given element would set in the filter
     pub fn hash_indices<'a>(&self, item: &'a impl AsRef<[u8]>) -> impl Iterator<Item = usize> + 'a {
         HashIndexIterator::new(item, self.bytes.len() * 8).take(self.hash_count())
     }
 }
 
+impl<'a> BloomFilterRef<'a> {
+    /// Construct a view of a filter with `k_hashes` hash functions and given bits.
+    pub fn new(k_hashes: usize, bytes: &'a [u8]) -> Self {
+        Self { k_hashes, bytes }
+    }
+
+    /// Compute the bloom parameters of the viewed filter.
+    pub fn get_bloom_params(&self) -> BloomParams {
+        BloomParams {
+            k_hashes: self.k_hashes,
+            byte_size: self.bytes.len(),
+        }
+    }
+
+    /// Check whether an element was (probably) added into the viewed filter.
+    ///
+    /// See [`BloomFilter::contains`].
+    pub fn contains(&self, item: &impl AsRef<[u8]>) -> bool {
+        let bits = self.bytes.view_bits::<Lsb0>();
+        self.hash_indices(item).all(|i| bits[i])
+    }
+
+    /// Counts the amount of bits set in the viewed filter.
+    pub fn count_ones(&self) -> usize {
+        self.bytes.view_bits::<Lsb0>().count_ones()
+    }
+
+    /// Returns how many hash function invocations are used per item inserted.
+    pub fn hash_count(&self) -> usize {
+        self.k_hashes
+    }
+
+    /// Return the borrowed bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Return the indices that a given element would set in the filter.
+    pub fn hash_indices<'b>(&self, item: &'b impl AsRef<[u8]>) -> impl Iterator<Item = usize> + 'b {
+        HashIndexIterator::new(item, self.bytes.len() * 8).take(self.k_hashes)
+    }
+
+    /// Check whether every bit set in this filter is also set in `other`.
+    ///
+    /// If this returns false, some item in this filter is definitely not in `other`.
+    /// Fails if the filters have different parameters.
+    pub fn is_subset_of(&self, other: &BloomFilterRef<'_>) -> Result<bool, Error> {
+        self.check_compatible(other)?;
+        Ok(self
+            .bytes
+            .iter()
+            .zip(other.bytes.iter())
+            .all(|(byte, other_byte)| byte & !other_byte == 0))
+    }
+
+    /// Check whether every bit set in `other` is also set in this filter.
+    ///
+    /// Fails if the filters have different parameters.
+    pub fn is_superset_of(&self, other: &BloomFilterRef<'_>) -> Result<bool, Error> {
+        other.is_subset_of(self)
+    }
+
+    /// Check whether the filters have no set bits in common.
+    ///
+    /// If this returns true, the filters definitely have no items in common.
+    /// Fails if the filters have different parameters.
+    pub fn is_disjoint_from(&self, other: &BloomFilterRef<'_>) -> Result<bool, Error> {
+        self.check_compatible(other)?;
+        Ok(self
+            .bytes
+            .iter()
+            .zip(other.bytes.iter())
+            .all(|(byte, other_byte)| byte & other_byte == 0))
+    }
+
+    /// Copy the viewed bytes into an owned [`BloomFilter`].
+    pub fn to_filter(&self) -> BloomFilter {
+        BloomFilter::new_with(self.k_hashes, Box::from(self.bytes))
+    }
+
+    fn check_compatible(&self, other: &BloomFilterRef<'_>) -> Result<(), Error> {
+        if self.get_bloom_params() != other.get_bloom_params() {
+            return Err(Error::BloomParamsMismatch {
+                expected: self.get_bloom_params(),
+                actual: other.get_bloom_params(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> From<&'a BloomFilter> for BloomFilterRef<'a> {
+    fn from(filter: &'a BloomFilter) -> Self {
+        filter.as_filter_ref()
+    }
+}
+
+impl Debug for BloomFilterRef<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BloomFilterRef")
+            .field("k_hashes", &self.k_hashes)
+            .field("bytes", &HexFieldDebug(self.bytes))
+            .finish()
+    }
+}
+
+#[cfg(feature = "ipld")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+impl libipld::codec::Encode<libipld::cbor::DagCborCodec> for BloomFilter {
+    fn encode<W: std::io::Write>(
+        &self,
+        c: libipld::cbor::DagCborCodec,
+        w: &mut W,
+    ) -> libipld::Result<()> {
+        self.to_ipld().encode(c, w)
+    }
+}
+
+#[cfg(feature = "ipld")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ipld")))]
+impl libipld::codec::Decode<libipld::cbor::DagCborCodec> for BloomFilter {
+    fn decode<R: std::io::Read + std::io::Seek>(
+        c: libipld::cbor::DagCborCodec,
+        r: &mut R,
+    ) -> libipld::Result<Self> {
+        let ipld: libipld::Ipld = libipld::codec::Decode::decode(c, r)?;
+        Ok(Self::from_ipld(&ipld)?)
+    }
+}
+
+/// Parses the [multibase string](BloomFilter::to_multibase_string) of a filter in any base.
+#[cfg(feature = "multibase")]
+#[cfg_attr(docsrs, doc(cfg(feature = "multibase")))]
+impl std::str::FromStr for BloomFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (_, bytes) = multibase::decode(s).map_err(|e| Error::InvalidMultibase {
+            reason: e.to_string(),
+        })?;
+        Self::from_container_bytes(&bytes)
+    }
+}
+
+/// Serializes as a tuple of `k_hashes` and the filter's bytes.
+impl Serialize for BloomFilter {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        (self.k_hashes as u64, SerializeBytes(&self.bytes)).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BloomFilter {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (k_hashes, DeserializeBytes(bytes)) =
+            <(u64, DeserializeBytes)>::deserialize(deserializer)?;
+
+        let k_hashes = usize::try_from(k_hashes)
+            .ok()
+            .filter(|&k_hashes| k_hashes != 0)
+            .ok_or_else(|| {
+                D::Error::custom(Error::MalformedEncoding {
+                    reason: "k_hashes must be non-zero",
+                })
+            })?;
+
+        Ok(Self::new_with(k_hashes, bytes.into_boxed_slice()))
+    }
+}
+
+#[cfg(feature = "constant_time")]
+#[cfg_attr(docsrs, doc(cfg(feature = "constant_time")))]
+impl subtle::ConstantTimeEq for BloomFilter {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.k_hashes.ct_eq(&other.k_hashes) & self.bytes.ct_eq(&other.bytes)
+    }
+}
+
+/// With the `constant_time` feature, `==` compares in constant time via [`ct_eq`](subtle::ConstantTimeEq::ct_eq).
+#[cfg(feature = "constant_time")]
+#[cfg_attr(docsrs, doc(cfg(feature = "constant_time")))]
+impl PartialEq for BloomFilter {
+    fn eq(&self, other: &Self) -> bool {
+        subtle::ConstantTimeEq::ct_eq(self, other).into()
+    }
+}
+
+#[cfg(feature = "constant_time")]
+#[cfg_attr(docsrs, doc(cfg(feature = "constant_time")))]
+impl zeroize::Zeroize for BloomFilter {
+    fn zeroize(&mut self) {
+        self.bytes.zeroize();
+    }
+}
+
+#[cfg(feature = "constant_time")]
+impl Drop for BloomFilter {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(self);
+    }
+}
+
 impl Debug for BloomFilter {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("BloomFilter")
@@ -270,6 +815,7 @@ impl Debug for BloomFilter {
 #[cfg(test)]
 mod tests {
     use super::BloomFilter;
+    use libipld::{cbor::DagCborCodec, codec::Codec, Ipld};
 
     #[test]
     fn serialization_round_trip() {
@@ -284,18 +830,109 @@ mod tests {
         assert_eq!(deserialized, filter);
     }
 
+    #[test]
+    fn serialized_bloom_filter_can_be_deserialized_correctly() {
+        let mut filter = BloomFilter::new_from_fpr(100, 0.001);
+        filter.insert(b"Hello");
+
+        let ipld = libipld::serde::to_ipld(&filter).unwrap();
+        let encoded = DagCborCodec.encode(&ipld).unwrap();
+        let decoded: Ipld = DagCborCodec.decode(&encoded).unwrap();
+        let deserialized: BloomFilter = libipld::serde::from_ipld(decoded).unwrap();
+
+        assert_eq!(deserialized, filter);
+        assert!(deserialized.contains(b"Hello"));
+    }
+
+    #[test]
+    fn deserializing_zero_hashes_fails() {
+        let filter = BloomFilter::new_with(0, Box::new([0u8; 8]));
+        let ipld = libipld::serde::to_ipld(&filter).unwrap();
+        assert!(libipld::serde::from_ipld::<BloomFilter>(ipld).is_err());
+    }
+
     #[test]
     fn empty_bloom_filter() {
         let filter = BloomFilter::new_with(3, Box::new([]));
         // Technically an empty bloom "contains" anything, since everything is a false positive.
         assert!(filter.contains(&[1, 2, 3]));
     }
+
+    #[cfg(feature = "constant_time")]
+    #[test]
+    fn constant_time_operations_agree() {
+        use subtle::ConstantTimeEq;
+
+        let mut filter = BloomFilter::new_from_fpr(100, 0.001);
+        filter.insert(b"Hello");
+        let other = filter.clone();
+
+        assert!(bool::from(filter.ct_eq(&other)));
+        assert_eq!(filter, other);
+        assert!(!bool::from(
+            filter.ct_eq(&BloomFilter::new_from_fpr(100, 0.001))
+        ));
+        for item in [b"Hello", b"World"] {
+            assert_eq!(bool::from(filter.ct_contains(item)), filter.contains(item));
+        }
+    }
+
+    #[test]
+    fn borrowed_views_agree_with_filters() {
+        let mut small = BloomFilter::new_from_fpr(100, 0.001);
+        let mut large = small.clone();
+        small.insert(b"Hello");
+        large.insert(b"Hello");
+        large.insert(b"World!");
+
+        let view = small.as_filter_ref();
+        assert!(view.contains(b"Hello"));
+        assert!(!view.contains(b"World!"));
+        assert_eq!(view.count_ones(), small.count_ones());
+        assert_eq!(view.to_filter(), small);
+
+        assert!(view.is_subset_of(&large.as_filter_ref()).unwrap());
+        assert!(large.as_filter_ref().is_superset_of(&view).unwrap());
+        assert!(!view.is_disjoint_from(&large.as_filter_ref()).unwrap());
+
+        let other = BloomFilter::new_from_fpr(200, 0.001);
+        assert!(view.is_subset_of(&other.as_filter_ref()).is_err());
+    }
+
+    #[test]
+    fn folding_requires_powers_of_two() {
+        let filter = BloomFilter::new_with(3, Box::new([0xAA; 16]));
+        assert_eq!(filter.fold(1).unwrap(), filter);
+        assert!(filter.fold(3).is_err());
+        assert!(filter.fold(32).is_err());
+        assert_eq!(filter.fold(16).unwrap().as_bytes(), &[0xAA]);
+
+        let filter = BloomFilter::new_with(3, Box::new([0; 12]));
+        assert!(filter.fold(2).is_err());
+        let mut other = BloomFilter::new_with(3, Box::new([0; 4]));
+        assert!(other.union_with_folding(&filter).is_err());
+        let mut other = BloomFilter::new_with(4, Box::new([0; 12]));
+        assert!(other.union_with_folding(&filter).is_err());
+
+        let mut other = BloomFilter::new_with(3, Box::new([0; 12]));
+        other.insert(b"Hello");
+        let mut union = filter.clone();
+        union.union_with_folding(&other).unwrap();
+        assert_eq!(union, other);
+    }
+
+    #[test]
+    fn union_requires_same_params() {
+        let mut filter = BloomFilter::new_from_size(100, 10);
+        let other = BloomFilter::new_from_size(200, 10);
+        assert!(filter.union_with(&other).is_err());
+    }
 }
 
 #[cfg(test)]
 mod proptests {
     use super::BloomFilter;
-    use proptest::prop_assert;
+    use proptest::{prop_assert, prop_assert_eq};
     use test_strategy::proptest;
 
     #[proptest]
@@ -312,6 +949,31 @@ mod proptests {
         }
     }
 
+    #[proptest]
+    fn folding_equals_inserting_into_smaller_filter(
+        items: Vec<u64>,
+        #[strategy(0u32..8)] size_log2: u32,
+        #[strategy(0u32..8)] fold_log2: u32,
+    ) {
+        let small_size = 1 << size_log2;
+        let mut large = BloomFilter::new_with(3, vec![0; small_size << fold_log2].into());
+        let mut small = BloomFilter::new_with(3, vec![0; small_size].into());
+
+        for item in items.iter() {
+            large.insert(&item.to_le_bytes());
+            small.insert(&item.to_le_bytes());
+        }
+
+        prop_assert_eq!(large.fold(1 << fold_log2).unwrap(), small.clone());
+
+        let mut union = BloomFilter::new_with(3, vec![0; small_size].into());
+        union.union_with_folding(&large).unwrap();
+        let mut other_union = large;
+        other_union.union_with_folding(&union).unwrap();
+        prop_assert_eq!(&union, &small);
+        prop_assert_eq!(&other_union, &small);
+    }
+
     #[proptest]
     fn false_positive_rate_as_predicted(
         #[strategy(100u64..1_000)] n_elems: u64,
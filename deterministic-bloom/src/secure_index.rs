@@ -0,0 +1,242 @@
+use crate::{common::BloomParams, runtime_size::BloomFilter};
+use bitvec::{prelude::Lsb0, view::BitView};
+use std::fmt::Debug;
+
+//------------------------------------------------------------------------------
+// Constants
+//------------------------------------------------------------------------------
+
+/// Domain separation tag for deriving trapdoors from words.
+const TRAPDOOR_TAG: u8 = 0;
+
+/// Domain separation tag for deriving the padding bits of an index.
+const PADDING_TAG: u8 = 1;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// The trapdoor for a word, which allows searching [`SecureIndex`]es for that word
+/// without revealing the word itself.
+///
+/// Trapdoors are computed with [`trapdoor`] by whoever holds the index key.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Trapdoor([u8; 32]);
+
+/// A [Goh-style secure index] for a single document.
+///
+/// Each word in the document is mapped to a trapdoor with a keyed PRF (keyed BLAKE3),
+/// and each trapdoor is blinded with the document identifier before it's inserted
+/// into the document's bloom filter. Because of the blinding, the same word sets
+/// unrelated bits in the indexes of different documents, so indexes can't be
+/// correlated with each other.
+///
+/// After inserting all words, the filter is saturated with pseudo-random bits until
+/// exactly half of its bits are set. This way all indexes with the same parameters
+/// look alike, no matter how many words their documents contain, as long as the
+/// documents don't exceed the capacity the parameters were chosen for.
+///
+/// Servers can store these indexes next to encrypted documents and, given a
+/// [`Trapdoor`], find the documents containing a word with [`search`]. Like any
+/// bloom filter query, searches can have false positives.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{common::BloomParams, secure_index};
+///
+/// let key = [42u8; 32];
+/// let params = BloomParams::new_from_fpr(100, 0.0001);
+///
+/// let index = secure_index::build_index(&key, b"document 1", ["secret", "words"], &params);
+/// assert_eq!(index.filter().count_ones(), index.filter().as_bytes().len() * 4);
+///
+/// assert!(secure_index::search(&index, &secure_index::trapdoor(&key, "secret")));
+/// assert!(!secure_index::search(&index, &secure_index::trapdoor(&key, "public")));
+/// ```
+///
+/// [Goh-style secure index]: https://eprint.iacr.org/2003/216
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecureIndex {
+    doc_id: Vec<u8>,
+    filter: BloomFilter,
+}
+
+//------------------------------------------------------------------------------
+// Functions
+//------------------------------------------------------------------------------
+
+/// Compute the trapdoor for searching indexes built with `key` for given word.
+pub fn trapdoor(key: &[u8; 32], word: impl AsRef<[u8]>) -> Trapdoor {
+    let mut hasher = blake3::Hasher::new_keyed(key);
+    hasher.update(&[TRAPDOOR_TAG]);
+    hasher.update(word.as_ref());
+    Trapdoor(*hasher.finalize().as_bytes())
+}
+
+/// Build the secure index for the document with identifier `doc_id` and given words.
+///
+/// Document identifiers need to be unique among all documents indexed with the same key.
+/// The index is deterministic, so rebuilding it for the same document gives the same index.
+pub fn build_index<T: AsRef<[u8]>>(
+    key: &[u8; 32],
+    doc_id: &[u8],
+    words: impl IntoIterator<Item = T>,
+    params: &BloomParams,
+) -> SecureIndex {
+    let mut filter = BloomFilter::new_with(
+        params.k_hashes,
+        vec![0u8; params.byte_size].into_boxed_slice(),
+    );
+    for word in words {
+        filter.insert(&codeword(&trapdoor(key, word), doc_id));
+    }
+
+    SecureIndex {
+        doc_id: doc_id.to_vec(),
+        filter: saturate(filter, key, doc_id),
+    }
+}
+
+/// Check whether the indexed document (probably) contains the trapdoor's word.
+pub fn search(index: &SecureIndex, trapdoor: &Trapdoor) -> bool {
+    index.filter.contains(&codeword(trapdoor, &index.doc_id))
+}
+
+/// Blinds a trapdoor with the document identifier.
+fn codeword(trapdoor: &Trapdoor, doc_id: &[u8]) -> [u8; 32] {
+    *blake3::keyed_hash(&trapdoor.0, doc_id).as_bytes()
+}
+
+/// Sets pseudo-random bits derived from the key and document identifier until
+/// half of the filter's bits are set.
+fn saturate(filter: BloomFilter, key: &[u8; 32], doc_id: &[u8]) -> BloomFilter {
+    let k_hashes = filter.hash_count();
+    let mut bytes = Box::<[u8]>::from(filter.as_bytes());
+    let bits = bytes.view_bits_mut::<Lsb0>();
+    let bit_size = bits.len();
+    let target = bit_size / 2;
+    let mut count = bits.count_ones();
+
+    let mut hasher = blake3::Hasher::new_keyed(key);
+    hasher.update(&[PADDING_TAG]);
+    hasher.update(doc_id);
+    let mut reader = hasher.finalize_xof();
+
+    let mask = bit_size.next_power_of_two() as u64 - 1;
+    let mut buffer = [0u8; 8];
+    while count < target {
+        reader.fill(&mut buffer);
+        let index = (u64::from_le_bytes(buffer) & mask) as usize;
+        if index < bit_size && !bits[index] {
+            bits.set(index, true);
+            count += 1;
+        }
+    }
+
+    BloomFilter::new_with(k_hashes, bytes)
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl Trapdoor {
+    /// Construct a trapdoor from its bytes, e.g. when it was sent over the network.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// Returns the bytes of this trapdoor.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl Debug for Trapdoor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Trapdoor").field(&"<redacted>").finish()
+    }
+}
+
+impl SecureIndex {
+    /// Construct an index from its parts, e.g. when deserializing.
+    pub fn new_with(doc_id: Vec<u8>, filter: BloomFilter) -> Self {
+        Self { doc_id, filter }
+    }
+
+    /// Returns the identifier of the indexed document.
+    pub fn doc_id(&self) -> &[u8] {
+        &self.doc_id
+    }
+
+    /// Returns the blinded, saturated bloom filter of this index.
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; 32] = [7u8; 32];
+
+    #[test]
+    fn padding_hides_word_counts() {
+        let params = BloomParams::new_from_fpr(100, 0.001);
+        let small = build_index(&KEY, b"small", ["one"], &params);
+        let large = build_index(&KEY, b"large", (0u32..100).map(u32::to_le_bytes), &params);
+
+        assert_eq!(small.filter().count_ones(), large.filter().count_ones());
+    }
+
+    #[test]
+    fn same_words_are_blinded_per_document() {
+        let params = BloomParams::new_from_fpr(100, 0.001);
+        let a = build_index(&KEY, b"a", ["word"], &params);
+        let b = build_index(&KEY, b"b", ["word"], &params);
+
+        assert_ne!(a.filter(), b.filter());
+        assert!(search(&a, &trapdoor(&KEY, "word")));
+        assert!(search(&b, &trapdoor(&KEY, "word")));
+    }
+
+    #[test]
+    fn other_keys_dont_match() {
+        let params = BloomParams::new_from_fpr(100, 0.0001);
+        let index = build_index(&KEY, b"doc", ["word"], &params);
+        assert!(!search(&index, &trapdoor(&[8u8; 32], "word")));
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::{build_index, search, trapdoor};
+    use crate::common::BloomParams;
+    use proptest::{collection::vec, prop_assert};
+    use test_strategy::proptest;
+
+    #[proptest(cases = 100)]
+    fn indexed_words_are_found(
+        #[strategy(vec(proptest::num::u64::ANY, 0..100))] words: Vec<u64>,
+        doc_id: Vec<u8>,
+    ) {
+        let key = [1u8; 32];
+        let params = BloomParams::new_from_fpr(100, 0.001);
+        let index = build_index(
+            &key,
+            &doc_id,
+            words.iter().map(|w| w.to_le_bytes()),
+            &params,
+        );
+
+        for word in words.iter() {
+            prop_assert!(search(&index, &trapdoor(&key, word.to_le_bytes())));
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use crate::{
+    common::{BloomParams, Error},
+    runtime_size::BloomFilter,
+};
+use std::cmp::Ordering;
+
+//------------------------------------------------------------------------------
+// Type Definitions
+//------------------------------------------------------------------------------
+
+/// An index over many [`BloomFilter`]s with the same parameters that answers
+/// "which stored filters are most similar to this one?" queries.
+///
+/// This is useful when bloom filters are used as set fingerprints, e.g. for
+/// picking peers with the most similar content.
+///
+/// Filters are kept sorted by their popcount. Since the popcount difference of two
+/// filters bounds both their Hamming and their Jaccard distance, a query only
+/// needs to look at filters with popcounts close to the query's popcount.
+///
+/// # Example
+///
+/// ```
+/// use deterministic_bloom::{
+///     runtime_size::BloomFilter,
+///     similarity::{Metric, SimilarityIndex},
+/// };
+///
+/// let make_filter = |items: std::ops::Range<u32>| {
+///     let mut filter = BloomFilter::new_from_size(128, 100);
+///     for i in items {
+///         filter.insert(&i.to_le_bytes());
+///     }
+///     filter
+/// };
+///
+/// let mut index = SimilarityIndex::new(make_filter(0..0).get_bloom_params());
+/// index.push(make_filter(0..50)).unwrap();
+/// index.push(make_filter(40..90)).unwrap();
+/// index.push(make_filter(10..60)).unwrap();
+///
+/// let nearest = index.nearest(&make_filter(5..55), 2, Metric::Jaccard).unwrap();
+/// let ids = nearest.iter().map(|neighbour| neighbour.id).collect::<Vec<_>>();
+/// assert_eq!(ids, vec![0, 2]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimilarityIndex {
+    params: BloomParams,
+    filters: Vec<BloomFilter>,
+    /// `(popcount, id)` pairs, sorted
+    by_popcount: Vec<(usize, usize)>,
+}
+
+/// The distance metric used for comparing filters in a [`SimilarityIndex`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    /// The number of bits that differ between two filters.
+    Hamming,
+    /// One minus the ratio of bits set in both filters to bits set in any of them.
+    /// Two empty filters have a Jaccard distance of zero.
+    Jaccard,
+}
+
+/// A filter found by [`SimilarityIndex::nearest`] together with its distance to the query.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Neighbour {
+    /// The id of the stored filter, as returned by [`SimilarityIndex::push`].
+    pub id: usize,
+    /// The distance to the query filter in the requested [`Metric`].
+    pub distance: f64,
+}
+
+//------------------------------------------------------------------------------
+// Implementations
+//------------------------------------------------------------------------------
+
+impl SimilarityIndex {
+    /// Creates an empty index for filters with given parameters.
+    pub fn new(params: BloomParams) -> Self {
+        Self {
+            params,
+            filters: Vec::new(),
+            by_popcount: Vec::new(),
+        }
+    }
+
+    /// Adds a filter to the index and returns its id.
+    ///
+    /// Fails if the filter's parameters differ from the index's parameters.
+    pub fn push(&mut self, filter: BloomFilter) -> Result<usize, Error> {
+        self.check_params(&filter)?;
+
+        let id = self.filters.len();
+        let entry = (filter.count_ones(), id);
+        let position = self.by_popcount.partition_point(|other| *other < entry);
+        self.by_popcount.insert(position, entry);
+        self.filters.push(filter);
+
+        Ok(id)
+    }
+
+    /// Returns the `k` stored filters closest to `query` in given metric, closest first.
+    /// Ties are broken by preferring lower ids.
+    ///
+    /// Fails if the query's parameters differ from the index's parameters.
+    pub fn nearest(
+        &self,
+        query: &BloomFilter,
+        k: usize,
+        metric: Metric,
+    ) -> Result<Vec<Neighbour>, Error> {
+        self.check_params(query)?;
+
+        let query_ones = query.count_ones();
+        let split = self
+            .by_popcount
+            .partition_point(|&(ones, _)| ones < query_ones);
+        let mut below = self.by_popcount[..split].iter().rev().peekable();
+        let mut above = self.by_popcount[split..].iter().peekable();
+
+        let mut best: Vec<Neighbour> = Vec::with_capacity(k + 1);
+
+        loop {
+            let bound = |entry: Option<&&(usize, usize)>| {
+                entry.map(|&&(ones, _)| metric.lower_bound(query_ones, ones))
+            };
+
+            let next = match (bound(below.peek()), bound(above.peek())) {
+                (None, None) => break,
+                (Some(_), None) => below.next(),
+                (None, Some(_)) => above.next(),
+                (Some(b), Some(a)) if b <= a => below.next(),
+                _ => above.next(),
+            };
+            let &(ones, id) = next.expect("peeked entry exists");
+
+            if best.len() == k {
+                match best.last() {
+                    Some(worst) if metric.lower_bound(query_ones, ones) <= worst.distance => {}
+                    // Every remaining filter is at least as far away as this one.
+                    _ => break,
+                }
+            }
+
+            let candidate = Neighbour {
+                id,
+                distance: metric.distance(query, &self.filters[id]),
+            };
+            let position = best.partition_point(|other| other.cmp_key(&candidate).is_lt());
+            best.insert(position, candidate);
+            best.truncate(k);
+        }
+
+        Ok(best)
+    }
+
+    /// Returns the filter with given id, if it exists.
+    pub fn get(&self, id: usize) -> Option<&BloomFilter> {
+        self.filters.get(id)
+    }
+
+    /// Returns the number of filters in this index.
+    pub fn len(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// Returns true if no filters were added to this index yet.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty()
+    }
+
+    /// Returns the parameters all filters in this index share.
+    pub fn params(&self) -> &BloomParams {
+        &self.params
+    }
+
+    fn check_params(&self, filter: &BloomFilter) -> Result<(), Error> {
+        let actual = filter.get_bloom_params();
+        if actual != self.params {
+            return Err(Error::BloomParamsMismatch {
+                expected: self.params.clone(),
+                actual,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Metric {
+    /// Compute the distance between two filters of the same size in this metric.
+    pub fn distance(&self, a: &BloomFilter, b: &BloomFilter) -> f64 {
+        let (mut both, mut any) = (0usize, 0usize);
+        for (x, y) in a.as_bytes().iter().zip(b.as_bytes()) {
+            both += (x & y).count_ones() as usize;
+            any += (x | y).count_ones() as usize;
+        }
+
+        match self {
+            Metric::Hamming => (any - both) as f64,
+            Metric::Jaccard if any == 0 => 0.0,
+            Metric::Jaccard => 1.0 - both as f64 / any as f64,
+        }
+    }
+
+    /// The smallest possible distance between two filters with given popcounts.
+    fn lower_bound(&self, ones_a: usize, ones_b: usize) -> f64 {
+        let (min, max) = (ones_a.min(ones_b), ones_a.max(ones_b));
+        match self {
+            Metric::Hamming => (max - min) as f64,
+            Metric::Jaccard if max == 0 => 0.0,
+            Metric::Jaccard => 1.0 - min as f64 / max as f64,
+        }
+    }
+}
+
+impl Neighbour {
+    fn cmp_key(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.id.cmp(&other.id))
+    }
+}
+
+//------------------------------------------------------------------------------
+// Tests
+//------------------------------------------------------------------------------
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mismatching_params_are_rejected() {
+        let mut index = SimilarityIndex::new(BloomParams::new_from_size(64, 10));
+        let result = index.push(BloomFilter::new_from_size(128, 10));
+        assert!(matches!(result, Err(Error::BloomParamsMismatch { .. })));
+    }
+
+    #[test]
+    fn empty_index_has_no_neighbours() {
+        let params = BloomParams::new_from_size(64, 10);
+        let index = SimilarityIndex::new(params);
+        let query = BloomFilter::new_from_size(64, 10);
+        assert!(index
+            .nearest(&query, 3, Metric::Hamming)
+            .unwrap()
+            .is_empty());
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::{Metric, SimilarityIndex};
+    use crate::runtime_size::BloomFilter;
+    use proptest::{collection::vec, prop_assert_eq};
+    use test_strategy::proptest;
+
+    fn filter_from(items: &[u8]) -> BloomFilter {
+        let mut filter = BloomFilter::new_from_size(16, 8);
+        for item in items {
+            filter.insert(&[*item]);
+        }
+        filter
+    }
+
+    #[proptest(cases = 200)]
+    fn nearest_matches_brute_force(
+        #[strategy(vec(vec(0u8..64, 0..20), 0..50))] sets: Vec<Vec<u8>>,
+        #[strategy(vec(0u8..64, 0..20))] query: Vec<u8>,
+        #[strategy(0usize..10)] k: usize,
+        jaccard: bool,
+    ) {
+        let metric = if jaccard {
+            Metric::Jaccard
+        } else {
+            Metric::Hamming
+        };
+        let query = filter_from(&query);
+        let mut index = SimilarityIndex::new(query.get_bloom_params());
+        for set in sets.iter() {
+            index.push(filter_from(set)).unwrap();
+        }
+
+        let mut expected = (0..index.len())
+            .map(|id| (metric.distance(&query, index.get(id).unwrap()), id))
+            .collect::<Vec<_>>();
+        expected.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        expected.truncate(k);
+
+        let actual = index
+            .nearest(&query, k, metric)
+            .unwrap()
+            .iter()
+            .map(|neighbour| (neighbour.distance, neighbour.id))
+            .collect::<Vec<_>>();
+
+        prop_assert_eq!(actual, expected);
+    }
+}
@@ -1,6 +1,6 @@
 //! Internally-used Utilities
 
-use serde::de::Visitor;
+use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt::{self, Debug};
 
 //--------------------------------------------------------------------------------------------------
@@ -9,6 +9,14 @@ use std::fmt::{self, Debug};
 
 pub(crate) struct ByteArrayVisitor<const N: usize>;
 
+pub(crate) struct ByteBufVisitor;
+
+/// Helper newtype for serializing a byte slice with `serialize_bytes`
+pub(crate) struct SerializeBytes<'a>(pub(crate) &'a [u8]);
+
+/// Helper newtype for deserializing a byte buffer with `deserialize_byte_buf`
+pub(crate) struct DeserializeBytes(pub(crate) Vec<u8>);
+
 /// Helper newtype for rendering given debug field as hex string
 pub(crate) struct HexFieldDebug<A: AsRef<[u8]>>(pub(crate) A);
 
@@ -32,6 +40,46 @@ impl<'de, const N: usize> Visitor<'de> for ByteArrayVisitor<N> {
     }
 }
 
+impl<'de> Visitor<'de> for ByteBufVisitor {
+    type Value = Vec<u8>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "a byte array")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(v.to_vec())
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(v)
+    }
+}
+
+impl Serialize for SerializeBytes<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for DeserializeBytes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Self(deserializer.deserialize_byte_buf(ByteBufVisitor)?))
+    }
+}
+
 impl<A: AsRef<[u8]>> Debug for HexFieldDebug<A> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "0x")?;
@@ -42,3 +90,129 @@ impl<A: AsRef<[u8]>> Debug for HexFieldDebug<A> {
         Ok(())
     }
 }
+
+//--------------------------------------------------------------------------------------------------
+// Functions
+//--------------------------------------------------------------------------------------------------
+
+/// Appends `value` to `bytes` as unsigned LEB128 varint.
+pub(crate) fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Reads an unsigned LEB128 varint from `bytes` starting at `*offset` and advances
+/// the offset past it. Returns `None` on truncated or overlong input.
+pub(crate) fn read_varint(bytes: &[u8], offset: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*offset)?;
+        *offset += 1;
+        let payload = (byte & 0x7F) as u64;
+        if shift == 63 && payload > 1 {
+            return None;
+        }
+        value |= payload << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+//--------------------------------------------------------------------------------------------------
+// Bit Streams
+//--------------------------------------------------------------------------------------------------
+
+/// Writes bits most-significant-bit first into a byte buffer, padding the last byte with zeros.
+#[derive(Debug, Default)]
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    bit_len: usize,
+}
+
+/// Reads bits most-significant-bit first from a byte buffer.
+#[derive(Debug)]
+pub(crate) struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl BitWriter {
+    pub(crate) fn write_bit(&mut self, bit: bool) {
+        if self.bit_len % 8 == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().expect("pushed above") |= 0x80 >> (self.bit_len % 8);
+        }
+        self.bit_len += 1;
+    }
+
+    /// Writes the lowest `count` bits of `value`.
+    pub(crate) fn write_bits(&mut self, value: u64, count: u32) {
+        for i in (0..count).rev() {
+            self.write_bit((value >> i) & 1 == 1);
+        }
+    }
+
+    /// Writes `value` Golomb-Rice coded with parameter `rice_bits`: the quotient in
+    /// unary (ones terminated by a zero), followed by the `rice_bits` lowest bits.
+    pub(crate) fn write_rice(&mut self, value: u64, rice_bits: u32) {
+        for _ in 0..(value >> rice_bits) {
+            self.write_bit(true);
+        }
+        self.write_bit(false);
+        self.write_bits(value, rice_bits);
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    pub(crate) fn read_bit(&mut self) -> Option<bool> {
+        let byte = self.bytes.get(self.position / 8)?;
+        let bit = byte & (0x80 >> (self.position % 8)) != 0;
+        self.position += 1;
+        Some(bit)
+    }
+
+    pub(crate) fn read_bits(&mut self, count: u32) -> Option<u64> {
+        let mut value = 0u64;
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit()? as u64;
+        }
+        Some(value)
+    }
+
+    /// Reads a value written with [`BitWriter::write_rice`].
+    pub(crate) fn read_rice(&mut self, rice_bits: u32) -> Option<u64> {
+        let mut quotient = 0u64;
+        while self.read_bit()? {
+            quotient += 1;
+        }
+        let remainder = self.read_bits(rice_bits)?;
+        quotient
+            .checked_shl(rice_bits)
+            .filter(|shifted| shifted >> rice_bits == quotient)
+            .map(|shifted| shifted | remainder)
+    }
+
+    /// Returns true if only zero padding bits are left in the current byte and no bytes follow.
+    pub(crate) fn only_padding_left(&self) -> bool {
+        let remaining_in_byte = (8 - self.position % 8) % 8;
+        (self.position + 7) / 8 == self.bytes.len()
+            && self.bytes.last().map_or(true, |last| {
+                last & ((1u16 << remaining_in_byte) - 1) as u8 == 0
+            })
+    }
+}